@@ -0,0 +1,68 @@
+//! Measures `IListPage::GetItems` and `ITreeContent::GetChildren` for large collections.
+//!
+//! Cached calls skip converting and filtering the items, but still add a reference
+//! to every element of the returned array, so they stay linear in the item count.
+//!
+//! Run with `cargo bench --bench get_items`.
+
+use cmdpal::bindings::{IListPage, ITreeContent};
+use cmdpal::cmd::InvokableCommandBuilder;
+use cmdpal::content::Content;
+use cmdpal::prelude::*;
+use std::time::{Duration, Instant};
+use windows::Win32::System::Com::{COINIT_MULTITHREADED, CoInitializeEx};
+
+const ITEM_COUNTS: [usize; 3] = [1_000, 10_000, 50_000];
+const ROUNDS: u32 = 20;
+
+fn measure(mut f: impl FnMut() -> WinResult<usize>) -> WinResult<(Duration, Duration)> {
+    let start = Instant::now();
+    f()?;
+    let first = start.elapsed();
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        f()?;
+    }
+    Ok((first, start.elapsed() / ROUNDS))
+}
+
+fn bench_list(count: usize) -> WinResult<()> {
+    let cmd = InvokableCommandBuilder::new(BaseCommandBuilder::new().build()).build();
+    let mut items = Vec::with_capacity(count);
+    for i in 0..count {
+        let item = CommandItemBuilder::try_new(cmd.to_interface())?
+            .title(format!("Item {i}"))
+            .build();
+        items.push(ListItemBuilder::new(item).build());
+    }
+    let page =
+        ListPageBuilder::new(BasePageBuilder::new(BaseCommandBuilder::new().build()).build())
+            .items(items)
+            .build();
+    let ipage: IListPage = page.to_interface();
+    let (first, cached) = measure(|| Ok(ipage.GetItems()?.len()))?;
+    println!("GetItems      {count:>6} items: first {first:?}, cached {cached:?}");
+    Ok(())
+}
+
+fn bench_tree(count: usize) -> WinResult<()> {
+    let children: Vec<Content> = (0..count)
+        .map(|i| MarkdownContent::new(format!("Child {i}")).into())
+        .collect();
+    let tree = TreeContentBuilder::new(MarkdownContent::new("Root").into())
+        .children(children)
+        .build();
+    let itree: ITreeContent = tree.to_interface();
+    let (first, cached) = measure(|| Ok(itree.GetChildren()?.len()))?;
+    println!("GetChildren   {count:>6} items: first {first:?}, cached {cached:?}");
+    Ok(())
+}
+
+fn main() -> WinResult<()> {
+    unsafe { CoInitializeEx(None, COINIT_MULTITHREADED).ok()? };
+    for count in ITEM_COUNTS {
+        bench_list(count)?;
+        bench_tree(count)?;
+    }
+    Ok(())
+}
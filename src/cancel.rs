@@ -0,0 +1,167 @@
+//! [`CancellationToken`] for stopping background work.
+//!
+//! The provider cancels its token when Command Palette closes it,
+//! see [`CommandProvider_Impl::cancellation_token`][`crate::cmd_provider::CommandProvider_Impl::cancellation_token`].
+//! Background threads, sockets and watchers started by the extension should stop once it is cancelled:
+//!
+//! ```ignore
+//! let token = provider.cancellation_token();
+//! std::thread::spawn(move || {
+//!     while !token.wait(Duration::from_secs(30)) {
+//!         poll_server();
+//!     }
+//! });
+//! ```
+
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use crate::error::catch_panic;
+
+type CancelCallback = Box<dyn Send + FnOnce()>;
+
+#[derive(Default)]
+struct CancelState {
+    cancelled: bool,
+    callbacks: Vec<CancelCallback>,
+}
+
+/// A shared flag which is set once, when the work it is handed to must stop.
+///
+/// Clones share the same flag.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<(Mutex<CancelState>, Condvar)>,
+}
+
+impl CancellationToken {
+    /// Creates a token which isn't cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the token, waking up waiting threads and calling the callbacks registered with
+    /// [`CancellationToken::on_cancel`].
+    ///
+    /// Cancelling a token again does nothing.
+    pub fn cancel(&self) {
+        let (state, wake) = &*self.inner;
+        let callbacks = match state.lock() {
+            Ok(mut state) if !state.cancelled => {
+                state.cancelled = true;
+                std::mem::take(&mut state.callbacks)
+            }
+            _ => return,
+        };
+        wake.notify_all();
+        for callback in callbacks {
+            let _ = catch_panic(|| {
+                callback();
+                Ok(())
+            });
+        }
+    }
+
+    /// Returns whether the token was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.inner
+            .0
+            .lock()
+            .map(|state| state.cancelled)
+            .unwrap_or(true)
+    }
+
+    /// Waits for `timeout`, returning whether the token was cancelled meanwhile.
+    ///
+    /// Returns immediately once the token is cancelled, so it can replace [`std::thread::sleep`] in worker loops.
+    pub fn wait(&self, timeout: Duration) -> bool {
+        let (state, wake) = &*self.inner;
+        let Ok(guard) = state.lock() else {
+            return true;
+        };
+        wake.wait_timeout_while(guard, timeout, |state| !state.cancelled)
+            .map(|(state, _)| state.cancelled)
+            .unwrap_or(true)
+    }
+
+    /// Registers `callback` to be called when the token is cancelled, e.g. to close a socket.
+    ///
+    /// The callback is called immediately if the token is already cancelled.
+    pub fn on_cancel<F>(&self, callback: F)
+    where
+        F: Send + FnOnce() + 'static,
+    {
+        if let Ok(mut state) = self.inner.0.lock()
+            && !state.cancelled
+        {
+            state.callbacks.push(Box::new(callback));
+            return;
+        }
+        let _ = catch_panic(|| {
+            callback();
+            Ok(())
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Instant;
+
+    #[test]
+    fn cancel_is_shared_by_clones() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert!(!clone.is_cancelled());
+        token.cancel();
+        assert!(clone.is_cancelled());
+        token.cancel();
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn wait_times_out_until_cancelled() {
+        let token = CancellationToken::new();
+        assert!(!token.wait(Duration::from_millis(10)));
+
+        let clone = token.clone();
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            clone.cancel();
+        });
+        let start = Instant::now();
+        assert!(token.wait(Duration::from_secs(10)));
+        assert!(start.elapsed() < Duration::from_secs(10));
+        canceller.join().unwrap();
+
+        assert!(token.wait(Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn callbacks_run_once() {
+        let token = CancellationToken::new();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        token.on_cancel(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+        token.cancel();
+        token.cancel();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn late_callback_runs_immediately() {
+        let token = CancellationToken::new();
+        token.cancel();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        token.on_cancel(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
@@ -3,6 +3,7 @@
 pub mod copy_text;
 pub mod open_url;
 pub mod reveal_file;
+pub mod run_process;
 
 #[doc(inline)]
 pub use copy_text::CopyTextCommandBuilder;
@@ -12,3 +13,6 @@ pub use open_url::OpenUrlCommandBuilder;
 
 #[doc(inline)]
 pub use reveal_file::RevealFileCommandBuilder;
+
+#[doc(inline)]
+pub use run_process::RunProcessCommandBuilder;
@@ -1,8 +1,11 @@
 //! Builder for creating commands that open URLs in the system's default browser.
 
+use std::sync::Arc;
+
 use crate::{
     cmd::{BaseCommand, BaseCommandBuilder, CommandResult, InvokableCommand},
     icon::{IconData, IconInfo},
+    platform::{Platform, default_platform},
     utils::ComBuilder,
 };
 use windows_core::ComObject;
@@ -12,6 +15,7 @@ pub struct OpenUrlCommandBuilder {
     base: ComObject<BaseCommand>,
     target_fn: Box<dyn Send + Sync + Fn() -> String>,
     result: CommandResult,
+    platform: Arc<dyn Platform>,
 }
 
 fn open_url_base_cmd() -> ComObject<BaseCommand> {
@@ -28,6 +32,7 @@ impl OpenUrlCommandBuilder {
             base: open_url_base_cmd(),
             target_fn: Box::new(move || target.clone()),
             result: CommandResult::Dismiss,
+            platform: default_platform(),
         }
     }
 
@@ -40,6 +45,7 @@ impl OpenUrlCommandBuilder {
             base: open_url_base_cmd(),
             target_fn: Box::new(target_fn),
             result: CommandResult::Dismiss,
+            platform: default_platform(),
         }
     }
 
@@ -58,32 +64,28 @@ impl OpenUrlCommandBuilder {
         self.result = result;
         self
     }
+
+    /// Overrides the platform used to open the URL.
+    ///
+    /// By default, the URL is opened with Win32 APIs.
+    pub fn platform(mut self, platform: Arc<dyn Platform>) -> Self {
+        self.platform = platform;
+        self
+    }
 }
 
 impl ComBuilder for OpenUrlCommandBuilder {
     type Output = InvokableCommand;
+    fn build(self) -> ComObject<InvokableCommand> {
+        self.build_unmanaged().into_object()
+    }
     fn build_unmanaged(self) -> InvokableCommand {
         InvokableCommand {
             base: self.base,
             func: Box::new(move |_| {
-                shell_helper::open_in_shell(&(self.target_fn)())?;
+                self.platform.open_in_shell(&(self.target_fn)())?;
                 Ok(self.result.clone())
             }),
         }
     }
 }
-
-mod shell_helper {
-    use windows::Win32::UI::Shell::{SHELLEXECUTEINFOW, ShellExecuteExW};
-    use windows::Win32::UI::{Shell::SEE_MASK_NOCLOSEPROCESS, WindowsAndMessaging::SW_SHOWNORMAL};
-
-    pub(super) fn open_in_shell(target: &str) -> windows_core::Result<()> {
-        let mut sei = SHELLEXECUTEINFOW::default();
-        sei.cbSize = std::mem::size_of::<SHELLEXECUTEINFOW>() as u32;
-        sei.fMask = SEE_MASK_NOCLOSEPROCESS;
-        sei.lpFile = windows_core::PCWSTR::from_raw(windows_core::HSTRING::from(target).as_ptr());
-        sei.nShow = SW_SHOWNORMAL.0;
-
-        unsafe { ShellExecuteExW(&mut sei) }
-    }
-}
@@ -1,17 +1,21 @@
 //! Builder for creating commands that reveals a path in the system's file explorer.
 
+use std::sync::Arc;
+
 use crate::{
     cmd::{BaseCommand, BaseCommandBuilder, CommandResult, InvokableCommand},
     icon::{IconData, IconInfo},
+    platform::{Platform, default_platform},
     utils::ComBuilder,
 };
-use windows::{Win32::Foundation::ERROR_FILE_INVALID, core::ComObject};
+use windows::core::ComObject;
 
 /// Builder for a command that reveals a file in the system's file explorer.
 pub struct RevealFileCommandBuilder {
     base: ComObject<BaseCommand>,
     path_fn: Box<dyn Send + Sync + Fn() -> std::path::PathBuf>,
     result: CommandResult,
+    platform: Arc<dyn Platform>,
 }
 
 fn reveal_file_base_cmd() -> ComObject<BaseCommand> {
@@ -28,6 +32,7 @@ impl RevealFileCommandBuilder {
             base: reveal_file_base_cmd(),
             path_fn: Box::new(move || path.clone()),
             result: CommandResult::Dismiss,
+            platform: default_platform(),
         }
     }
 
@@ -40,6 +45,7 @@ impl RevealFileCommandBuilder {
             base: reveal_file_base_cmd(),
             path_fn: Box::new(path_fn),
             result: CommandResult::Dismiss,
+            platform: default_platform(),
         }
     }
 
@@ -54,44 +60,29 @@ impl RevealFileCommandBuilder {
         self.result = result;
         self
     }
+
+    /// Sets the platform used to reveal the file.
+    ///
+    /// By default, the file is revealed with Win32 APIs,
+    /// failing if the path doesn't exist.
+    pub fn platform(mut self, platform: Arc<dyn Platform>) -> Self {
+        self.platform = platform;
+        self
+    }
 }
 
 impl ComBuilder for RevealFileCommandBuilder {
     type Output = InvokableCommand;
+    fn build(self) -> ComObject<InvokableCommand> {
+        self.build_unmanaged().into_object()
+    }
     fn build_unmanaged(self) -> InvokableCommand {
         InvokableCommand {
             base: self.base,
             func: Box::new(move |_| {
-                let path = (self.path_fn)()
-                    .canonicalize()
-                    .map_err(|_| windows_core::Error::from(ERROR_FILE_INVALID))?;
-                match path.try_exists() {
-                    Ok(true) => {
-                        explorer_helper::reveal_file(&path.to_string_lossy().replace("/", r"\"))?;
-                        Ok(self.result.clone())
-                    }
-                    _ => Err(windows_core::Error::from(ERROR_FILE_INVALID)),
-                }
+                self.platform.reveal_file(&(self.path_fn)())?;
+                Ok(self.result.clone())
             }),
         }
     }
 }
-
-mod explorer_helper {
-    use windows::Win32::UI::Shell::{SHELLEXECUTEINFOW, ShellExecuteExW};
-    use windows::Win32::UI::{Shell::SEE_MASK_NOCLOSEPROCESS, WindowsAndMessaging::SW_SHOWNORMAL};
-    use windows_core::{HSTRING, PCWSTR, w};
-
-    pub(super) fn reveal_file(target: &str) -> windows_core::Result<()> {
-        let params = format!("/select,\"{}\"", target);
-        println!("Revealing file in explorer with params: {}", params);
-        let mut sei = SHELLEXECUTEINFOW::default();
-        sei.cbSize = std::mem::size_of::<SHELLEXECUTEINFOW>() as u32;
-        sei.fMask = SEE_MASK_NOCLOSEPROCESS;
-        sei.lpFile = w!("explorer.exe");
-        sei.lpParameters = PCWSTR::from_raw(HSTRING::from(params).as_ptr());
-        sei.nShow = SW_SHOWNORMAL.0;
-
-        unsafe { ShellExecuteExW(&mut sei) }
-    }
-}
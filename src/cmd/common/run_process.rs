@@ -0,0 +1,487 @@
+//! Builder for creating commands that run external programs.
+
+use std::ffi::OsString;
+use std::io::Read;
+#[cfg(windows)]
+use std::os::windows::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::{
+    cmd::{BaseCommand, BaseCommandBuilder, CommandResult, InvokableCommand},
+    cmd_result::ToastArgs,
+    content::markdown::MarkdownContent,
+    host::{MessageState, StatusContext, StatusMessageBuilder, show_status_for},
+    icon::{IconData, IconInfo},
+    platform::{Platform, default_platform},
+    utils::ComBuilder,
+};
+use windows::Win32::Foundation::{E_FAIL, ERROR_TIMEOUT};
+use windows_core::{ComObject, Error, HSTRING, Result, h};
+
+/// `CREATE_NO_WINDOW` process creation flag, so console programs don't flash a window.
+#[cfg(windows)]
+const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+/// How long error status messages of failed processes stay visible.
+const ERROR_STATUS_DURATION: Duration = Duration::from_secs(8);
+
+/// What to do with the output of a process.
+pub enum ProcessOutput {
+    /// Don't capture the output, and don't wait for the process before returning.
+    Detached,
+    /// Show the standard output as a toast.
+    ///
+    /// If the output is empty, no toast is shown and the result is returned directly.
+    Toast,
+    /// Copy the standard output to the clipboard.
+    Copy,
+    /// Write the standard output and error into a [`MarkdownContent`] as a code block.
+    ///
+    /// The process runs in the background, and the content is updated when it exits.
+    Markdown(ComObject<MarkdownContent>),
+}
+
+/// Builder for a command that runs an external program.
+///
+/// If the process exits with a non-zero code or times out,
+/// an error status message is shown to the user, including the last line of its standard error.
+/// Failing to start the process fails the invocation.
+pub struct RunProcessCommandBuilder {
+    base: ComObject<BaseCommand>,
+    program: OsString,
+    args: Vec<OsString>,
+    current_dir: Option<PathBuf>,
+    envs: Vec<(OsString, OsString)>,
+    timeout: Option<Duration>,
+    console_window: bool,
+    output: ProcessOutput,
+    result: Option<CommandResult>,
+    platform: Arc<dyn Platform>,
+}
+
+fn run_process_base_cmd() -> ComObject<BaseCommand> {
+    BaseCommandBuilder::new()
+        .name("Run")
+        .icon(IconInfo::new(IconData::from("\u{E756}")))
+        .build()
+}
+
+impl RunProcessCommandBuilder {
+    /// Creates a new command builder that runs `program` in [`ProcessOutput::Detached`] mode.
+    pub fn new(program: impl Into<OsString>) -> Self {
+        Self {
+            base: run_process_base_cmd(),
+            program: program.into(),
+            args: Vec::new(),
+            current_dir: None,
+            envs: Vec::new(),
+            timeout: None,
+            console_window: false,
+            output: ProcessOutput::Detached,
+            result: None,
+            platform: default_platform(),
+        }
+    }
+
+    /// Sets the base command for this command.
+    ///
+    /// By default, the base command has name "Run" with a command prompt icon "\u{E756}".
+    pub fn base(mut self, base: ComObject<BaseCommand>) -> Self {
+        self.base = base;
+        self
+    }
+
+    /// Adds an argument to pass to the program.
+    pub fn arg(mut self, arg: impl Into<OsString>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Adds multiple arguments to pass to the program.
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets the working directory of the process.
+    pub fn current_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    /// Sets an environment variable of the process.
+    ///
+    /// The process inherits the environment of the extension otherwise.
+    pub fn env(mut self, key: impl Into<OsString>, value: impl Into<OsString>) -> Self {
+        self.envs.push((key.into(), value.into()));
+        self
+    }
+
+    /// Sets the time after which the process is killed and considered failed.
+    ///
+    /// Without a timeout, the command waits for the process for as long as it runs.
+    /// Consider setting one for [`ProcessOutput::Toast`] and [`ProcessOutput::Copy`],
+    /// as they block the invocation until the process exits.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets whether console programs get a console window.
+    ///
+    /// By default, processes are created with `CREATE_NO_WINDOW`, so console programs don't flash a window.
+    /// Enable this for interactive console tools, e.g. a shell in [`ProcessOutput::Detached`] mode.
+    pub fn console_window(mut self, console_window: bool) -> Self {
+        self.console_window = console_window;
+        self
+    }
+
+    /// Sets what to do with the output of the process.
+    ///
+    /// Defaults to [`ProcessOutput::Detached`].
+    pub fn output(mut self, output: ProcessOutput) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// Sets the result to be returned when the command is executed.
+    ///
+    /// For [`ProcessOutput::Toast`] and [`ProcessOutput::Copy`], this is the result after the toast.
+    /// By default, the result is [`CommandResult::KeepOpen`] for [`ProcessOutput::Markdown`],
+    /// and [`CommandResult::Dismiss`] otherwise.
+    pub fn result(mut self, result: CommandResult) -> Self {
+        self.result = Some(result);
+        self
+    }
+
+    /// Sets the platform used to access the clipboard for [`ProcessOutput::Copy`].
+    ///
+    /// By default, the clipboard is accessed with Win32 APIs.
+    pub fn platform(mut self, platform: Arc<dyn Platform>) -> Self {
+        self.platform = platform;
+        self
+    }
+}
+
+/// A configured process invocation, shared by all invocations of the command.
+struct ProcessSpec {
+    program: OsString,
+    args: Vec<OsString>,
+    current_dir: Option<PathBuf>,
+    envs: Vec<(OsString, OsString)>,
+    timeout: Option<Duration>,
+    console_window: bool,
+}
+
+/// Captured output of a finished process.
+struct Finished {
+    status: ExitStatus,
+    stdout: String,
+    stderr: String,
+}
+
+impl ProcessSpec {
+    fn name(&self) -> String {
+        PathBuf::from(&self.program)
+            .file_name()
+            .unwrap_or(&self.program)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn spawn(&self, capture: bool) -> Result<Child> {
+        let stdio = || {
+            if capture {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            }
+        };
+        let mut command = Command::new(&self.program);
+        command
+            .args(&self.args)
+            .envs(self.envs.iter().map(|(k, v)| (k, v)))
+            .stdin(Stdio::null())
+            .stdout(stdio())
+            .stderr(stdio());
+        #[cfg(windows)]
+        if !self.console_window {
+            command.creation_flags(CREATE_NO_WINDOW);
+        }
+        if let Some(dir) = &self.current_dir {
+            command.current_dir(dir);
+        }
+        command
+            .spawn()
+            .map_err(|e| Error::new(E_FAIL, format!("Failed to start {}: {}", self.name(), e)))
+    }
+
+    /// Waits for `child` to exit, killing it once the timeout elapses.
+    fn wait(&self, mut child: Child) -> Result<Finished> {
+        fn read_all(pipe: Option<impl Read + Send + 'static>) -> std::thread::JoinHandle<String> {
+            std::thread::spawn(move || {
+                let mut buf = Vec::new();
+                if let Some(mut pipe) = pipe {
+                    let _ = pipe.read_to_end(&mut buf);
+                }
+                String::from_utf8_lossy(&buf).into_owned()
+            })
+        }
+        let stdout = read_all(child.stdout.take());
+        let stderr = read_all(child.stderr.take());
+
+        let io_err = |e: std::io::Error| {
+            Error::new(E_FAIL, format!("Failed to wait for {}: {}", self.name(), e))
+        };
+        let deadline = self.timeout.map(|t| Instant::now() + t);
+        let status = loop {
+            let Some(deadline) = deadline else {
+                break child.wait().map_err(io_err)?;
+            };
+            if let Some(status) = child.try_wait().map_err(io_err)? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(Error::new(
+                    ERROR_TIMEOUT.to_hresult(),
+                    format!("{} timed out", self.name()),
+                ));
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        Ok(Finished {
+            status,
+            stdout: stdout.join().unwrap_or_default(),
+            stderr: stderr.join().unwrap_or_default(),
+        })
+    }
+
+    /// Returns the captured output of a successful process, or the error message for a failed one.
+    fn outcome(&self, finished: Result<Finished>) -> std::result::Result<Finished, String> {
+        match finished {
+            Ok(finished) if finished.status.success() => Ok(finished),
+            Ok(finished) => {
+                let code = finished
+                    .status
+                    .code()
+                    .map_or_else(|| "unknown".to_string(), |c| c.to_string());
+                let line = finished.stderr.lines().rev().find(|l| !l.trim().is_empty());
+                Err(match line {
+                    Some(line) => format!("{} exited with code {}: {}", self.name(), code, line),
+                    None => format!("{} exited with code {}", self.name(), code),
+                })
+            }
+            Err(e) => Err(e.message()),
+        }
+    }
+
+    /// Returns the captured output of a successful process,
+    /// or shows an error status message for a failed one.
+    fn check(&self, finished: Result<Finished>) -> Option<Finished> {
+        let message = match self.outcome(finished) {
+            Ok(finished) => return Some(finished),
+            Err(message) => message,
+        };
+        let status = StatusMessageBuilder::new()
+            .state(MessageState::Error)
+            .message(message.into())
+            .build();
+        show_status_for(
+            status.into(),
+            StatusContext::Extension,
+            ERROR_STATUS_DURATION,
+        );
+        None
+    }
+}
+
+/// Formats the output of `finished` as a code block.
+///
+/// The fence is longer than any run of backticks in the output, so the output can't close it.
+fn markdown_output(finished: &Finished) -> String {
+    let longest_run = [&finished.stdout, &finished.stderr]
+        .iter()
+        .flat_map(|text| text.split(|c| c != '`'))
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    let mut body = format!("{fence}\n");
+    body.push_str(&finished.stdout);
+    if !finished.stderr.is_empty() {
+        if !body.ends_with('\n') {
+            body.push('\n');
+        }
+        body.push_str(&finished.stderr);
+    }
+    if !body.ends_with('\n') {
+        body.push('\n');
+    }
+    body.push_str(&fence);
+    body.push('\n');
+    body
+}
+
+impl ComBuilder for RunProcessCommandBuilder {
+    type Output = InvokableCommand;
+    fn build(self) -> ComObject<InvokableCommand> {
+        self.build_unmanaged().into_object()
+    }
+    fn build_unmanaged(self) -> InvokableCommand {
+        let spec = Arc::new(ProcessSpec {
+            program: self.program,
+            args: self.args,
+            current_dir: self.current_dir,
+            envs: self.envs,
+            timeout: self.timeout,
+            console_window: self.console_window,
+        });
+        let output = self.output;
+        let platform = self.platform;
+        let result = self.result.unwrap_or(match output {
+            ProcessOutput::Markdown(_) => CommandResult::KeepOpen,
+            _ => CommandResult::Dismiss,
+        });
+        InvokableCommand {
+            base: self.base,
+            func: Box::new(move |_| match &output {
+                ProcessOutput::Detached => {
+                    let child = spec.spawn(false)?;
+                    let spec = spec.clone();
+                    std::thread::spawn(move || spec.check(spec.wait(child)));
+                    Ok(result.clone())
+                }
+                ProcessOutput::Toast => {
+                    let child = spec.spawn(true)?;
+                    match spec.check(spec.wait(child)) {
+                        Some(finished) if finished.stdout.trim().is_empty() => Ok(result.clone()),
+                        Some(finished) => Ok(CommandResult::ShowToast(ToastArgs::new(
+                            finished.stdout.trim(),
+                            result.clone(),
+                        )?)),
+                        None => Ok(CommandResult::KeepOpen),
+                    }
+                }
+                ProcessOutput::Copy => {
+                    let child = spec.spawn(true)?;
+                    match spec.check(spec.wait(child)) {
+                        Some(finished) => {
+                            platform.set_clipboard_text(&finished.stdout.trim().into())?;
+                            Ok(CommandResult::ShowToast(ToastArgs::new(
+                                h!("Copied to clipboard").clone(),
+                                result.clone(),
+                            )?))
+                        }
+                        None => Ok(CommandResult::KeepOpen),
+                    }
+                }
+                ProcessOutput::Markdown(content) => {
+                    let child = spec.spawn(true)?;
+                    *content.body_mut()? = HSTRING::from(format!("Running `{}`...", spec.name()));
+                    let spec = spec.clone();
+                    let content = content.clone();
+                    std::thread::spawn(move || {
+                        let finished = spec.wait(child);
+                        let body = match &finished {
+                            Ok(finished) => markdown_output(finished),
+                            Err(e) => e.message(),
+                        };
+                        if let Ok(mut guard) = content.body_mut() {
+                            *guard = body.into();
+                        }
+                        spec.check(finished);
+                    });
+                    Ok(result.clone())
+                }
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::windows::process::ExitStatusExt;
+
+    fn process(program: &str, args: &[&str], timeout: Option<Duration>) -> ProcessSpec {
+        ProcessSpec {
+            program: program.into(),
+            args: args.iter().map(Into::into).collect(),
+            current_dir: None,
+            envs: Vec::new(),
+            timeout,
+            console_window: false,
+        }
+    }
+
+    fn finished(stdout: &str, stderr: &str) -> Finished {
+        Finished {
+            status: ExitStatus::from_raw(0),
+            stdout: stdout.to_string(),
+            stderr: stderr.to_string(),
+        }
+    }
+
+    #[test]
+    fn wait_kills_process_after_timeout() {
+        let spec = process(
+            "ping",
+            &["-n", "30", "127.0.0.1"],
+            Some(Duration::from_millis(200)),
+        );
+        let start = Instant::now();
+        let error = spec.wait(spec.spawn(false).unwrap()).err().unwrap();
+        assert_eq!(error.code(), ERROR_TIMEOUT.to_hresult());
+        assert_eq!(error.message(), "ping timed out");
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn wait_captures_output() {
+        let spec = process("cmd", &["/C", "echo out& echo err 1>&2"], None);
+        let finished = spec.wait(spec.spawn(true).unwrap()).unwrap();
+        assert!(finished.status.success());
+        assert_eq!(finished.stdout.trim(), "out");
+        assert_eq!(finished.stderr.trim(), "err");
+    }
+
+    #[test]
+    fn outcome_reports_exit_code_and_last_error_line() {
+        let spec = process(
+            "cmd",
+            &["/C", "echo first 1>&2& echo last 1>&2& exit 3"],
+            None,
+        );
+        let message = spec
+            .outcome(spec.wait(spec.spawn(true).unwrap()))
+            .err()
+            .unwrap();
+        assert_eq!(message, "cmd exited with code 3: last");
+
+        let spec = process("cmd", &["/C", "exit 2"], None);
+        let message = spec
+            .outcome(spec.wait(spec.spawn(true).unwrap()))
+            .err()
+            .unwrap();
+        assert_eq!(message, "cmd exited with code 2");
+    }
+
+    #[test]
+    fn markdown_fence_outlasts_backticks_in_output() {
+        assert_eq!(markdown_output(&finished("a", "")), "```\na\n```\n");
+        assert_eq!(markdown_output(&finished("a\n", "b")), "```\na\nb\n```\n");
+        assert_eq!(
+            markdown_output(&finished("```rust\n", "`````")),
+            "``````\n```rust\n`````\n``````\n"
+        );
+    }
+}
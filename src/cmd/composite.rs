@@ -0,0 +1,252 @@
+//! Commands composed of other [`InvokableCommand`]s.
+//!
+//! Combine existing commands, e.g. from [`crate::cmd::common`], instead of duplicating their logic:
+//!
+//! - [`SequenceCommandBuilder`] runs several commands in order,
+//! - [`ConditionalCommandBuilder`] picks a command at invoke time,
+//! - [`ConfirmedCommandBuilder`] asks the user for confirmation before invoking a command,
+//! - [`InvokableCommand_Impl::then`][`super::InvokableCommand_Impl::then`] overrides the result of a command.
+//!
+//! Composed commands have their own [`BaseCommand`], so their own id.
+//! Wrappers of a single command derive it from the wrapped command, see [`BaseCommand_Impl::derive`][`super::BaseCommand_Impl::derive`].
+
+use crate::cmd_result::{ConfirmationArgsBuilder, ToastArgs};
+use crate::utils::ComBuilder;
+use windows_core::{ComObject, HSTRING, IInspectable, Result};
+
+use super::{BaseCommand, BaseCommandBuilder, CommandResult, InvokableCommand};
+
+/// Builder for a command which invokes several commands in order.
+///
+/// Invocation stops at the first command which fails, returning its error.
+/// Otherwise, the result of the last command is returned, unless overridden by [`SequenceCommandBuilder::result`].
+///
+/// Results of earlier commands are handled as follows:
+///
+/// - [`CommandResult::ShowToast`] messages are kept. The sequence shows one toast with all messages,
+///   one per line, followed by the result of the sequence.
+/// - [`CommandResult::GoToPage`] and [`CommandResult::Confirm`] end the sequence,
+///   since the user has to see the page or confirm first. The remaining commands are not invoked,
+///   and the result is returned, after the toast of earlier commands if any, even if overridden.
+/// - Other results are discarded.
+pub struct SequenceCommandBuilder {
+    base: ComObject<BaseCommand>,
+    commands: Vec<ComObject<InvokableCommand>>,
+    result: Option<CommandResult>,
+}
+
+impl SequenceCommandBuilder {
+    /// Creates a new builder with no commands.
+    pub fn new(base: ComObject<BaseCommand>) -> Self {
+        SequenceCommandBuilder {
+            base,
+            commands: Vec::new(),
+            result: None,
+        }
+    }
+
+    /// Adds a command to the end of the sequence.
+    pub fn add_command(mut self, command: ComObject<InvokableCommand>) -> Self {
+        self.commands.push(command);
+        self
+    }
+
+    /// Sets the result returned after all commands succeeded.
+    ///
+    /// By default, the result of the last command is returned,
+    /// or [`CommandResult::KeepOpen`] if there are no commands.
+    pub fn result(mut self, result: CommandResult) -> Self {
+        self.result = Some(result);
+        self
+    }
+}
+
+impl ComBuilder for SequenceCommandBuilder {
+    type Output = InvokableCommand;
+    fn build(self) -> ComObject<InvokableCommand> {
+        self.build_unmanaged().into_object()
+    }
+    fn build_unmanaged(self) -> InvokableCommand {
+        let commands = self.commands;
+        let overridden = self.result;
+        InvokableCommand {
+            base: self.base,
+            func: Box::new(move |sender| {
+                let mut messages = Vec::new();
+                let mut result = CommandResult::KeepOpen;
+                for command in commands.iter() {
+                    let mut next = command.invoke(sender)?;
+                    while let CommandResult::ShowToast(toast) = next {
+                        messages.push(toast.message.to_string_lossy());
+                        next = toast.result.clone();
+                    }
+                    if matches!(next, CommandResult::GoToPage(_) | CommandResult::Confirm(_)) {
+                        return with_toast(messages, next);
+                    }
+                    result = next;
+                }
+                with_toast(messages, overridden.clone().unwrap_or(result))
+            }),
+        }
+    }
+}
+
+/// Shows `messages` as one toast followed by `result`, or returns `result` if there are none.
+fn with_toast(messages: Vec<String>, result: CommandResult) -> Result<CommandResult> {
+    if messages.is_empty() {
+        return Ok(result);
+    }
+    Ok(CommandResult::ShowToast(ToastArgs::new(
+        messages.join("\n"),
+        result,
+    )?))
+}
+
+type ConditionBox = Box<dyn Send + Sync + Fn(&IInspectable) -> Result<bool>>;
+
+/// Builder for a command which picks the command to invoke when it is invoked.
+///
+/// Conditions are checked in the order they were added,
+/// and the command of the first one which holds is invoked.
+/// If none holds, the [`ConditionalCommandBuilder::otherwise`] command is invoked,
+/// or [`CommandResult::KeepOpen`] is returned if there is none.
+pub struct ConditionalCommandBuilder {
+    base: ComObject<BaseCommand>,
+    branches: Vec<(ConditionBox, ComObject<InvokableCommand>)>,
+    otherwise: Option<ComObject<InvokableCommand>>,
+}
+
+impl ConditionalCommandBuilder {
+    /// Creates a new builder with no branches.
+    pub fn new(base: ComObject<BaseCommand>) -> Self {
+        ConditionalCommandBuilder {
+            base,
+            branches: Vec::new(),
+            otherwise: None,
+        }
+    }
+
+    /// Adds a branch invoking `command` if `condition` holds.
+    ///
+    /// The condition receives the sender of the invocation.
+    pub fn when<F>(mut self, condition: F, command: ComObject<InvokableCommand>) -> Self
+    where
+        F: Send + Sync + Fn(&IInspectable) -> Result<bool> + 'static,
+    {
+        self.branches.push((Box::new(condition), command));
+        self
+    }
+
+    /// Sets the command invoked if no condition holds.
+    pub fn otherwise(mut self, command: ComObject<InvokableCommand>) -> Self {
+        self.otherwise = Some(command);
+        self
+    }
+}
+
+impl ComBuilder for ConditionalCommandBuilder {
+    type Output = InvokableCommand;
+    fn build(self) -> ComObject<InvokableCommand> {
+        self.build_unmanaged().into_object()
+    }
+    fn build_unmanaged(self) -> InvokableCommand {
+        let branches = self.branches;
+        let otherwise = self.otherwise;
+        InvokableCommand {
+            base: self.base,
+            func: Box::new(move |sender| {
+                for (condition, command) in branches.iter() {
+                    if condition(sender)? {
+                        return command.invoke(sender);
+                    }
+                }
+                match &otherwise {
+                    Some(command) => command.invoke(sender),
+                    None => Ok(CommandResult::KeepOpen),
+                }
+            }),
+        }
+    }
+}
+
+/// Builder for a command which asks the user for confirmation before invoking another command.
+///
+/// Invoking the built command returns [`CommandResult::Confirm`],
+/// and the wrapped command is only invoked once the user confirms.
+/// The built command has the name and icon of the wrapped command, and its own id derived from it,
+/// see [`BaseCommand_Impl::derive`][`super::BaseCommand_Impl::derive`].
+///
+/// Critical context items can opt into this with
+/// [`CommandContextItemBuilder::confirm`][`crate::ctx_item::CommandContextItemBuilder::confirm`].
+pub struct ConfirmedCommandBuilder {
+    inner: ComObject<InvokableCommand>,
+    title: Option<HSTRING>,
+    description: HSTRING,
+    critical: bool,
+}
+
+impl ConfirmedCommandBuilder {
+    /// Creates a new builder which confirms invocations of `inner`.
+    pub fn new(inner: ComObject<InvokableCommand>) -> Self {
+        ConfirmedCommandBuilder {
+            inner,
+            title: None,
+            description: HSTRING::new(),
+            critical: false,
+        }
+    }
+
+    /// Sets the title of the confirmation.
+    ///
+    /// Defaults to the name of the wrapped command at the time of invocation.
+    pub fn title(mut self, title: impl Into<HSTRING>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Sets the description of the confirmation.
+    pub fn description(mut self, description: impl Into<HSTRING>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    /// Sets whether the wrapped command is critical, e.g. destructive.
+    ///
+    /// The host highlights the confirming button of critical commands.
+    pub fn critical(mut self, critical: bool) -> Self {
+        self.critical = critical;
+        self
+    }
+}
+
+impl ComBuilder for ConfirmedCommandBuilder {
+    type Output = InvokableCommand;
+    fn build(self) -> ComObject<InvokableCommand> {
+        self.build_unmanaged().into_object()
+    }
+    fn build_unmanaged(self) -> InvokableCommand {
+        let inner = self.inner;
+        let title = self.title;
+        let description = self.description;
+        let critical = self.critical;
+        let base = inner
+            .base
+            .derive("confirm")
+            .unwrap_or_else(|_| BaseCommandBuilder::new().build());
+        InvokableCommand {
+            base,
+            func: Box::new(move |_| {
+                let title = match &title {
+                    Some(title) => title.clone(),
+                    None => inner.name()?.clone(),
+                };
+                let args = ConfirmationArgsBuilder::try_new(inner.to_interface())?
+                    .title(title)
+                    .description(description.clone())
+                    .is_critical(critical)
+                    .build();
+                Ok(CommandResult::Confirm(args))
+            }),
+        }
+    }
+}
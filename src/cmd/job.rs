@@ -0,0 +1,340 @@
+//! Commands running long jobs in the background, with their progress shown as a status message.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::cmd_item::{CommandItem, CommandItemBuilder};
+use crate::cmd_result::ToastArgs;
+use crate::error::catch_panic;
+use crate::host::{
+    MessageState, ProgressState, ProgressStateBuilder, StatusContext, StatusMessage,
+    StatusMessageBuilder, hide_status, show_status,
+};
+use crate::icon::{IconData, IconInfo};
+use crate::utils::ComBuilder;
+use windows::Win32::Foundation::ERROR_LOCK_VIOLATION;
+use windows_core::{ComObject, Error, HSTRING, Result};
+
+use super::transient::TransientItems;
+use super::{BaseCommand, BaseCommand_Impl, BaseCommandBuilder, CommandResult, InvokableCommand};
+
+type JobBox = Box<dyn Send + Sync + Fn(&ProgressReporter) -> Result<()>>;
+
+/// Reports the progress of a background job through its status message.
+pub struct ProgressReporter {
+    status: ComObject<StatusMessage>,
+    progress: ComObject<ProgressState>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl ProgressReporter {
+    /// Sets the message of the status.
+    pub fn message(&self, message: impl Into<HSTRING>) -> Result<()> {
+        *self.status.message_mut()? = message.into();
+        Ok(())
+    }
+
+    /// Sets the progress percentage, from 0 to 100.
+    pub fn percentage(&self, percentage: u32) -> Result<()> {
+        if *self.progress.indeterminate()? {
+            *self.progress.indeterminate_mut()? = false;
+        }
+        *self.progress.percentage_mut()? = percentage.min(100);
+        Ok(())
+    }
+
+    /// Shows the progress as indeterminate, which is the initial state.
+    pub fn indeterminate(&self) -> Result<()> {
+        *self.progress.indeterminate_mut()? = true;
+        Ok(())
+    }
+
+    /// Returns whether the job was cancelled by a "Cancel" command.
+    ///
+    /// Jobs should check this regularly, and return early once cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// A running job, keyed by the order it was started in.
+struct RunningJob {
+    key: u64,
+    cancelled: Arc<AtomicBool>,
+}
+
+/// State shared between the invocations of a job command and its "Cancel" commands.
+struct JobState {
+    job: JobBox,
+    title: Option<HSTRING>,
+    success_message: Option<HSTRING>,
+    duration: Duration,
+    running: Mutex<Vec<RunningJob>>,
+    next_key: AtomicU64,
+    shown: TransientItems<u64>,
+}
+
+impl JobState {
+    fn lock_running(&self) -> Result<std::sync::MutexGuard<'_, Vec<RunningJob>>> {
+        self.running
+            .lock()
+            .map_err(|_| Error::from(ERROR_LOCK_VIOLATION))
+    }
+
+    /// Creates a "Cancel" command for the job with `key`, or for the latest running job if `None`.
+    ///
+    /// Its id is the id of `base` followed by `.cancel`, and the key of the job if any.
+    fn cancel_command(
+        self: &Arc<Self>,
+        base: &BaseCommand_Impl,
+        key: Option<u64>,
+    ) -> ComObject<InvokableCommand> {
+        let mut cancel_base = BaseCommandBuilder::new()
+            .name("Cancel")
+            .icon(IconInfo::new(IconData::from("\u{E711}")));
+        if let Ok(id) = base.id()
+            && !id.is_empty()
+        {
+            cancel_base = cancel_base.id(match key {
+                Some(key) => format!("{}.cancel.{}", *id, key),
+                None => format!("{}.cancel", *id),
+            });
+        }
+        if let Some(registry) = base.registry() {
+            cancel_base = cancel_base.registry(registry);
+        }
+        let state = self.clone();
+        InvokableCommand {
+            base: cancel_base.build(),
+            func: Box::new(move |_| {
+                let message = match state.cancel(key)? {
+                    true => "Cancelling...",
+                    false => "Nothing to cancel",
+                };
+                Ok(CommandResult::ShowToast(ToastArgs::new(
+                    message,
+                    CommandResult::KeepOpen,
+                )?))
+            }),
+        }
+        .into_object()
+    }
+
+    /// Marks a job as running, showing a "Cancel" context item for it on the target.
+    fn start(
+        self: &Arc<Self>,
+        base: &BaseCommand_Impl,
+        name: &HSTRING,
+    ) -> Result<(u64, Arc<AtomicBool>)> {
+        let key = self.next_key.fetch_add(1, Ordering::Relaxed) + 1;
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.lock_running()?.push(RunningJob {
+            key,
+            cancelled: cancelled.clone(),
+        });
+        let shown = self.shown.show(key, || {
+            let cancel = self.cancel_command(base, Some(key));
+            Ok(CommandItemBuilder::try_new(cancel.to_interface())?
+                .subtitle(format!("{} #{}", name, key))
+                .build())
+        });
+        if let Err(e) = shown {
+            self.finish(key);
+            return Err(e);
+        }
+        Ok((key, cancelled))
+    }
+
+    /// Marks the job with `key` as finished, removing its "Cancel" context item.
+    fn finish(&self, key: u64) {
+        if let Ok(mut running) = self.running.lock() {
+            running.retain(|job| job.key != key);
+        }
+        self.shown.hide(&key);
+    }
+
+    /// Requests cancellation of the job with `key`, or of the latest running job not cancelled yet if `None`.
+    ///
+    /// Returns whether such a job was running.
+    fn cancel(&self, key: Option<u64>) -> Result<bool> {
+        let running = self.lock_running()?;
+        let job = match key {
+            Some(key) => running.iter().find(|job| job.key == key),
+            None => running
+                .iter()
+                .rev()
+                .find(|job| !job.cancelled.load(Ordering::Relaxed)),
+        };
+        match job {
+            Some(job) => {
+                job.cancelled.store(true, Ordering::Relaxed);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Runs the job on the current thread, driving its status message.
+    ///
+    /// Returns the status message, showing the final status.
+    fn run(&self, name: HSTRING, cancelled: Arc<AtomicBool>) -> ComObject<StatusMessage> {
+        let progress = ProgressStateBuilder::new().build();
+        let status: ComObject<StatusMessage> = StatusMessageBuilder::new()
+            .progress(progress.clone())
+            .message(
+                self.title
+                    .clone()
+                    .unwrap_or_else(|| format!("{}...", name).into()),
+            )
+            .build()
+            .into();
+        show_status(status.clone(), StatusContext::Extension);
+
+        let reporter = ProgressReporter {
+            status: status.clone(),
+            progress: progress.clone(),
+            cancelled,
+        };
+        let result = catch_panic(|| (self.job)(&reporter));
+
+        let (state, message) = match result {
+            _ if reporter.is_cancelled() => (MessageState::Info, format!("{} cancelled", name)),
+            Ok(()) => (
+                MessageState::Success,
+                self.success_message
+                    .as_ref()
+                    .map(|m| m.to_string_lossy())
+                    .unwrap_or_else(|| format!("{} finished", name)),
+            ),
+            Err(e) => (MessageState::Error, e.message()),
+        };
+        if state == MessageState::Success {
+            let _ = reporter.percentage(100);
+        }
+        if let Ok(mut guard) = status.state_mut() {
+            *guard = state;
+        }
+        let _ = reporter.message(message);
+        status
+    }
+}
+
+/// Builder for a command which runs a long job in the background.
+///
+/// Invoking the command starts the job on a worker thread and returns immediately.
+/// While the job runs, a status message shows its progress, reported through a [`ProgressReporter`].
+/// Once the job ends, the status shows whether it succeeded, failed or was cancelled, then hides.
+///
+/// Each running job can be cancelled by its own "Cancel" command, shown as a context item of the command item set with
+/// [`BackgroundJobCommandBuilder::cancel_in`].
+/// The "Cancel" command returned by [`BackgroundJobCommandBuilder::build_with_cancel`] can be placed anywhere else,
+/// it cancels the latest running job.
+/// Cancellation is cooperative, jobs should check [`ProgressReporter::is_cancelled`].
+pub struct BackgroundJobCommandBuilder {
+    base: ComObject<BaseCommand>,
+    job: JobBox,
+    title: Option<HSTRING>,
+    success_message: Option<HSTRING>,
+    duration: Duration,
+    result: CommandResult,
+    target: Option<ComObject<CommandItem>>,
+}
+
+impl BackgroundJobCommandBuilder {
+    /// Creates a new builder which runs `job` in the background when invoked.
+    pub fn new<F>(base: ComObject<BaseCommand>, job: F) -> Self
+    where
+        F: Send + Sync + Fn(&ProgressReporter) -> Result<()> + 'static,
+    {
+        BackgroundJobCommandBuilder {
+            base,
+            job: Box::new(job),
+            title: None,
+            success_message: None,
+            duration: Duration::from_secs(5),
+            result: CommandResult::KeepOpen,
+            target: None,
+        }
+    }
+
+    /// Sets the initial message of the status. Defaults to the name of the command followed by "...".
+    pub fn title(mut self, title: impl Into<HSTRING>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Sets the message shown when the job succeeds. Defaults to the name of the command followed by "finished".
+    pub fn success_message(mut self, message: impl Into<HSTRING>) -> Self {
+        self.success_message = Some(message.into());
+        self
+    }
+
+    /// Sets how long the final status stays visible. Defaults to 5 seconds.
+    pub fn duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// Sets the result returned when the job is started. Defaults to [`CommandResult::KeepOpen`].
+    pub fn result(mut self, result: CommandResult) -> Self {
+        self.result = result;
+        self
+    }
+
+    /// Shows a "Cancel" command as a context item of `item` for each running job.
+    pub fn cancel_in(mut self, item: ComObject<CommandItem>) -> Self {
+        self.target = Some(item);
+        self
+    }
+
+    /// Builds the command along with a "Cancel" command cancelling the latest running job.
+    ///
+    /// Invoking the "Cancel" command while no job is running only shows a toast.
+    pub fn build_with_cancel(self) -> (ComObject<InvokableCommand>, ComObject<InvokableCommand>) {
+        let (cmd, cancel) = self.build_parts();
+        (cmd.into_object(), cancel)
+    }
+
+    fn build_parts(self) -> (InvokableCommand, ComObject<InvokableCommand>) {
+        let state = Arc::new(JobState {
+            job: self.job,
+            title: self.title,
+            success_message: self.success_message,
+            duration: self.duration,
+            running: Mutex::new(Vec::new()),
+            next_key: AtomicU64::new(0),
+            shown: TransientItems::new(self.target),
+        });
+        let cancel = state.cancel_command(&self.base, None);
+
+        let base = self.base.clone();
+        let result = self.result;
+        let cmd = InvokableCommand {
+            base: self.base,
+            func: Box::new(move |_| {
+                let name = base.name()?.clone();
+                let (key, cancelled) = state.start(&base, &name)?;
+                let state = state.clone();
+                std::thread::spawn(move || {
+                    let status = state.run(name, cancelled);
+                    state.finish(key);
+                    std::thread::sleep(state.duration);
+                    hide_status(status);
+                });
+                Ok(result.clone())
+            }),
+        };
+        (cmd, cancel)
+    }
+}
+
+impl ComBuilder for BackgroundJobCommandBuilder {
+    type Output = InvokableCommand;
+    fn build(self) -> ComObject<InvokableCommand> {
+        self.build_unmanaged().into_object()
+    }
+    fn build_unmanaged(self) -> InvokableCommand {
+        self.build_parts().0
+    }
+}
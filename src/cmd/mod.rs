@@ -1,15 +1,37 @@
 //! Commands - the foundation of Command Palette.
 
 pub mod common;
+pub mod composite;
+pub mod job;
+pub mod undo;
+
+mod throttle;
+mod transient;
 
 use std::ops::Deref;
+use std::sync::Arc;
+use std::time::Duration;
 
 use crate::bindings::*;
+use crate::cmd_provider::CommandRegistry;
 pub use crate::cmd_result::CommandResult;
+use crate::error::{BoxError, ErrorPolicy, catch_panic, into_win_error};
+use crate::frecency::Frecency;
+use crate::host::LogMessage;
 use crate::icon::IconInfo;
 use crate::notify::*;
+use crate::page::BasePage;
+use crate::page::content::ContentPage;
+use crate::page::dyn_list::DynamicListPage;
+use crate::page::list::ListPage;
 use crate::utils::{ComBuilder, OkOrEmpty};
-use windows_core::{ComObject, Event, HSTRING, IInspectable, IUnknownImpl as _, implement};
+use windows_core::{
+    ComObject, ComObjectInner, ComObjectInterface, HSTRING, IInspectable, IUnknownImpl as _,
+    implement,
+};
+
+use throttle::Throttle;
+pub use throttle::WhenBusy;
 
 /// Represents basic properties of a command.
 /// 
@@ -21,7 +43,8 @@ pub struct BaseCommand {
     name: NotifyLock<HSTRING>,
     id: NotifyLock<HSTRING>,
     icon: NotifyLock<Option<ComObject<IconInfo>>>,
-    event: PropChangedEventHandler,
+    registry: Option<CommandRegistry>,
+    event: TrackedEvent<PropChangedEventHandler>,
 }
 
 /// Builder for [`BaseCommand`].
@@ -29,6 +52,7 @@ pub struct BaseCommandBuilder {
     name: HSTRING,
     id: HSTRING,
     icon: Option<ComObject<IconInfo>>,
+    registry: Option<CommandRegistry>,
 }
 
 impl BaseCommandBuilder {
@@ -38,6 +62,7 @@ impl BaseCommandBuilder {
             name: HSTRING::new(),
             id: HSTRING::new(),
             icon: None,
+            registry: None,
         }
     }
 
@@ -58,16 +83,30 @@ impl BaseCommandBuilder {
         self.icon = Some(icon);
         self
     }
+
+    /// Sets the registry the command is registered in, usually the one of its provider.
+    ///
+    /// Commands built on this base command, e.g. with [`InvokableCommandBuilder`] or a page builder,
+    /// are registered under the id of the command when they are built,
+    /// so `GetCommand` resolves them wherever they are reachable from.
+    pub fn registry(mut self, registry: &CommandRegistry) -> Self {
+        self.registry = Some(registry.clone());
+        self
+    }
 }
 
 impl ComBuilder for BaseCommandBuilder {
     type Output = BaseCommand;
+    fn build(self) -> ComObject<BaseCommand> {
+        registered(self.build_unmanaged().into())
+    }
     fn build_unmanaged(self) -> BaseCommand {
         BaseCommand {
             name: NotifyLock::new(self.name),
             id: NotifyLock::new(self.id),
             icon: NotifyLock::new(self.icon),
-            event: Event::new(),
+            registry: self.registry,
+            event: TrackedEvent::new(),
         }
     }
 }
@@ -96,6 +135,12 @@ impl ICommand_Impl for BaseCommand_Impl {
     }
 }
 
+impl Observable for BaseCommand {
+    fn is_observed(this: &BaseCommand_Impl) -> bool {
+        this.event.has_handlers()
+    }
+}
+
 impl INotifyPropChanged_Impl for BaseCommand_Impl {
     fn PropChanged(
         &self,
@@ -127,6 +172,33 @@ impl BaseCommand_Impl {
         self.emit_prop_changed(self.to_interface(), prop);
     }
 
+    /// Creates a base command for a command wrapping this one.
+    ///
+    /// It has the current name and icon of this command, and the id of this command followed by `.{suffix}`,
+    /// or no id if this command has none, so Command Palette can tell the wrapper from the wrapped command.
+    /// It shares the registry of this command, if any.
+    pub fn derive(&self, suffix: &str) -> windows_core::Result<ComObject<BaseCommand>> {
+        let id = self.id()?.clone();
+        let mut builder = BaseCommandBuilder::new().name(self.name()?.clone());
+        if !id.is_empty() {
+            builder = builder.id(format!("{}.{}", id, suffix));
+        }
+        if let Some(icon) = self.icon()?.clone() {
+            builder = builder.icon(icon);
+        }
+        if let Some(registry) = &self.registry {
+            builder = builder.registry(registry);
+        }
+        Ok(builder.build())
+    }
+
+    /// Returns the registry the command is registered in, if any.
+    ///
+    /// See [`BaseCommandBuilder::registry`].
+    pub fn registry(&self) -> Option<&CommandRegistry> {
+        self.registry.as_ref()
+    }
+
     /// Readonly access to [`ICommand::Name`].
     ///
     #[doc = include_str!("../bindings_docs/ICommand/Name.md")]
@@ -157,8 +229,15 @@ impl BaseCommand_Impl {
     #[doc = include_str!("../bindings_docs/ICommand/Id.md")]
     ///
     /// Notifies the host about the property change when dropping the guard.
+    /// Commands registered under the previous id are no longer resolved by it,
+    /// commands reachable from the items of the provider are found under the new id.
     pub fn id_mut(&self) -> windows_core::Result<NotifyLockWriteGuard<'_, windows_core::HSTRING>> {
-        self.id.write(|| self.emit_self_prop_changed("Id"))
+        self.id.write(|| {
+            if let Some(registry) = &self.registry {
+                registry.forget_misses();
+            }
+            self.emit_self_prop_changed("Id")
+        })
     }
 
     /// Readonly access to [`ICommand::Icon`].
@@ -197,6 +276,9 @@ pub struct InvokableCommand {
 pub struct InvokableCommandBuilder {
     base: ComObject<BaseCommand>,
     func: InvokableBox,
+    usage: Option<Arc<Frecency>>,
+    error_policy: Option<ErrorPolicy>,
+    throttle: Throttle,
 }
 
 impl InvokableCommandBuilder {
@@ -207,6 +289,9 @@ impl InvokableCommandBuilder {
         Self {
             base,
             func: Box::new(|_| Ok(CommandResult::KeepOpen)),
+            usage: None,
+            error_policy: None,
+            throttle: Throttle::new(),
         }
     }
 
@@ -231,15 +316,164 @@ impl InvokableCommandBuilder {
         self.func = Box::new(move |_| func());
         self
     }
+
+    /// Like [`InvokableCommandBuilder::func`], but the function may return any error,
+    /// e.g. `anyhow::Error`, converted with [`into_win_error`].
+    pub fn func_any<F, E>(mut self, func: F) -> Self
+    where
+        F: Send + Sync + Fn(&IInspectable) -> Result<CommandResult, E> + 'static,
+        E: Into<BoxError>,
+    {
+        self.func = Box::new(move |sender| func(sender).map_err(into_win_error));
+        self
+    }
+
+    /// Like [`InvokableCommandBuilder::anon_func`], but the function may return any error,
+    /// e.g. `anyhow::Error`, converted with [`into_win_error`].
+    pub fn anon_func_any<F, E>(mut self, func: F) -> Self
+    where
+        F: Send + Sync + Fn() -> Result<CommandResult, E> + 'static,
+        E: Into<BoxError>,
+    {
+        self.func = Box::new(move |_| func().map_err(into_win_error));
+        self
+    }
+
+    /// Sets how errors of the invocation function are reported to the user.
+    ///
+    /// By default, errors are returned to Command Palette without feedback to the user.
+    pub fn error_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.error_policy = Some(policy);
+        self
+    }
+
+    /// Runs at most one invocation at a time, ignoring or queueing the others.
+    ///
+    /// Guards against Command Palette firing an invocation twice, or the user repeatedly pressing Enter.
+    /// Shorthand for [`InvokableCommandBuilder::concurrency_limit`] with a limit of 1.
+    pub fn single_flight(self, when_busy: WhenBusy) -> Self {
+        self.concurrency_limit(1, when_busy)
+    }
+
+    /// Runs at most `limit` invocations at a time, ignoring or queueing the others.
+    ///
+    /// Ignored invocations return [`CommandResult::KeepOpen`].
+    /// Queued invocations block until a running one finishes.
+    pub fn concurrency_limit(mut self, limit: usize, when_busy: WhenBusy) -> Self {
+        self.throttle.limit = Some(limit.max(1));
+        self.throttle.when_busy = when_busy;
+        self
+    }
+
+    /// Ignores invocations within `cooldown` after the previous invocation finished.
+    ///
+    /// Ignored invocations return [`CommandResult::KeepOpen`].
+    pub fn cooldown(mut self, cooldown: Duration) -> Self {
+        self.throttle.cooldown = Some(cooldown);
+        self
+    }
+
+    /// Sets the name of the command while it is running, e.g. "Uploading...".
+    ///
+    /// The name is restored once no invocation is running.
+    pub fn busy_name(mut self, name: impl Into<HSTRING>) -> Self {
+        self.throttle.busy_name = Some(name.into());
+        self
+    }
+
+    /// Sets the icon of the command while it is running.
+    ///
+    /// The icon is restored once no invocation is running.
+    pub fn busy_icon(mut self, icon: ComObject<IconInfo>) -> Self {
+        self.throttle.busy_icon = Some(icon);
+        self
+    }
+
+    /// Records each successful invocation in a [`Frecency`] tracker.
+    ///
+    /// Invocations are recorded by the [`ICommand::Id`] of the command at the time of invocation,
+    /// commands with an empty id are not recorded.
+    /// Failing to persist the usage data doesn't fail the invocation.
+    pub fn track_usage(mut self, frecency: Arc<Frecency>) -> Self {
+        self.usage = Some(frecency);
+        self
+    }
 }
 
 impl ComBuilder for InvokableCommandBuilder {
     type Output = InvokableCommand;
+    fn build(self) -> ComObject<InvokableCommand> {
+        self.build_unmanaged().into_object()
+    }
     fn build_unmanaged(self) -> InvokableCommand {
+        let func = match self.usage {
+            Some(frecency) => {
+                let base = self.base.clone();
+                let inner = self.func;
+                Box::new(move |sender: &IInspectable| {
+                    let result = inner(sender)?;
+                    if let Ok(id) = base.id()
+                        && !id.is_empty()
+                    {
+                        let _ = frecency.record(&id.to_string_lossy());
+                    }
+                    Ok(result)
+                }) as InvokableBox
+            }
+            None => self.func,
+        };
+        let func = match self.error_policy {
+            Some(policy) => Box::new(move |sender: &IInspectable| {
+                policy.handle_result(catch_panic(|| func(sender)))
+            }) as InvokableBox,
+            None => func,
+        };
+        let func = match self.throttle.is_set() {
+            true => {
+                let base = self.base.clone();
+                let throttle = self.throttle;
+                Box::new(move |sender: &IInspectable| throttle.run(&base, || func(sender)))
+                    as InvokableBox
+            }
+            false => func,
+        };
         InvokableCommand {
             base: self.base,
-            func: self.func,
+            func,
+        }
+    }
+}
+
+impl InvokableCommand_Impl {
+    /// Runs the invocation function of the command directly, without going through COM.
+    ///
+    /// Useful for composing commands, see [`composite`].
+    pub fn invoke(&self, sender: &IInspectable) -> windows_core::Result<CommandResult> {
+        (self.func)(sender)
+    }
+
+    /// Creates a command which invokes this command, then returns `result` instead of its result.
+    ///
+    /// The new command has the name and icon of this command, and its own id derived from it,
+    /// see [`BaseCommand_Impl::derive`].
+    /// Errors of this command are returned unchanged.
+    pub fn then(&self, result: CommandResult) -> windows_core::Result<ComObject<InvokableCommand>> {
+        let inner: ComObject<InvokableCommand> = self.to_object();
+        Ok(InvokableCommand {
+            base: self.base.derive("then")?,
+            func: Box::new(move |sender| {
+                inner.invoke(sender)?;
+                Ok(result.clone())
+            }),
         }
+        .into_object())
+    }
+}
+
+impl InvokableCommand {
+    /// Like [`ComBuilder::build`], for commands assembled without a builder.
+    pub(crate) fn into_object(self) -> ComObject<InvokableCommand> {
+        registered(self.into())
     }
 }
 
@@ -255,8 +489,7 @@ impl IInvokableCommand_Impl for InvokableCommand_Impl {
         &self,
         sender: windows_core::Ref<'_, windows_core::IInspectable>,
     ) -> windows_core::Result<ICommandResult> {
-        let result = (self.func)(sender.ok()?);
-        result.map(|r| r.into())
+        catch_panic(|| self.invoke(sender.ok()?)).map(|r| r.into())
     }
 }
 
@@ -292,3 +525,43 @@ impl INotifyPropChanged_Impl for InvokableCommand_Impl {
         self.base.RemovePropChanged(token)
     }
 }
+
+/// Returns the base command of `command`, if it is a command of this crate.
+pub(crate) fn base_command(command: &ICommand) -> Option<ComObject<BaseCommand>> {
+    if let Ok(command) = ComObject::<InvokableCommand>::cast_from(command) {
+        return Some(command.base.clone());
+    }
+    if let Ok(page) = ComObject::<DynamicListPage>::cast_from(command) {
+        return Some(page.base.base.base.clone());
+    }
+    if let Ok(page) = ComObject::<ListPage>::cast_from(command) {
+        return Some(page.base.base.clone());
+    }
+    if let Ok(page) = ComObject::<ContentPage>::cast_from(command) {
+        return Some(page.base.base.clone());
+    }
+    if let Ok(page) = ComObject::<BasePage>::cast_from(command) {
+        return Some(page.base.clone());
+    }
+    ComObject::<BaseCommand>::cast_from(command).ok()
+}
+
+/// Registers a newly built command in the registry of its base command, if it has one,
+/// and returns it.
+///
+/// Builders of commands and pages call it from [`ComBuilder::build`].
+/// Failures are logged, see [`CommandRegistry::register`].
+pub(crate) fn registered<T>(object: ComObject<T>) -> ComObject<T>
+where
+    T: ComObjectInner,
+    T::Outer: ComObjectInterface<ICommand>,
+{
+    let command: ICommand = object.to_interface();
+    if let Some(base) = base_command(&command)
+        && let Some(registry) = base.registry()
+        && let Err(e) = registry.register(&command)
+    {
+        LogMessage::warning(e.message().into()).log();
+    }
+    object
+}
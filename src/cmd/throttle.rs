@@ -0,0 +1,147 @@
+//! Limits on how often and how concurrently a command runs.
+
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::icon::IconInfo;
+use windows::Win32::Foundation::ERROR_LOCK_VIOLATION;
+use windows_core::{ComObject, Error, HSTRING, Result};
+
+use super::{BaseCommand_Impl, CommandResult};
+
+/// What to do with an invocation while the concurrency limit of a command is reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WhenBusy {
+    /// Return [`CommandResult::KeepOpen`] without running the command.
+    #[default]
+    Ignore,
+    /// Block until a running invocation finishes, then run the command.
+    Queue,
+}
+
+#[derive(Default)]
+struct ThrottleState {
+    running: usize,
+    last_end: Option<Instant>,
+    saved_name: Option<HSTRING>,
+    saved_icon: Option<Option<ComObject<IconInfo>>>,
+}
+
+pub(crate) struct Throttle {
+    pub(crate) limit: Option<usize>,
+    pub(crate) when_busy: WhenBusy,
+    pub(crate) cooldown: Option<Duration>,
+    pub(crate) busy_name: Option<HSTRING>,
+    pub(crate) busy_icon: Option<ComObject<IconInfo>>,
+    state: Mutex<ThrottleState>,
+    idle: Condvar,
+}
+
+/// Marks an invocation as running until dropped, even if the command panics.
+struct Running<'a> {
+    throttle: &'a Throttle,
+    base: &'a BaseCommand_Impl,
+}
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.throttle.state.lock() {
+            state.running -= 1;
+            state.last_end = Some(Instant::now());
+            if state.running == 0 {
+                self.throttle.leave_busy(&mut state, self.base);
+            }
+        }
+        self.throttle.idle.notify_all();
+    }
+}
+
+impl Throttle {
+    pub(crate) fn new() -> Self {
+        Throttle {
+            limit: None,
+            when_busy: WhenBusy::Ignore,
+            cooldown: None,
+            busy_name: None,
+            busy_icon: None,
+            state: Mutex::new(ThrottleState::default()),
+            idle: Condvar::new(),
+        }
+    }
+
+    /// Returns whether any limit or busy state is set.
+    pub(crate) fn is_set(&self) -> bool {
+        self.limit.is_some()
+            || self.cooldown.is_some()
+            || self.busy_name.is_some()
+            || self.busy_icon.is_some()
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, ThrottleState>> {
+        self.state
+            .lock()
+            .map_err(|_| Error::from(ERROR_LOCK_VIOLATION))
+    }
+
+    fn enter_busy(&self, state: &mut ThrottleState, base: &BaseCommand_Impl) {
+        if let Some(name) = &self.busy_name
+            && let Ok(mut guard) = base.name_mut()
+        {
+            state.saved_name = Some(std::mem::replace(&mut *guard, name.clone()));
+        }
+        if let Some(icon) = &self.busy_icon
+            && let Ok(mut guard) = base.icon_mut()
+        {
+            state.saved_icon = Some(guard.replace(icon.clone()));
+        }
+    }
+
+    fn leave_busy(&self, state: &mut ThrottleState, base: &BaseCommand_Impl) {
+        if let Some(name) = state.saved_name.take()
+            && let Ok(mut guard) = base.name_mut()
+        {
+            *guard = name;
+        }
+        if let Some(icon) = state.saved_icon.take()
+            && let Ok(mut guard) = base.icon_mut()
+        {
+            *guard = icon;
+        }
+    }
+
+    /// Runs `f` within the limits, or returns [`CommandResult::KeepOpen`] if it must not run.
+    pub(crate) fn run(
+        &self,
+        base: &BaseCommand_Impl,
+        f: impl FnOnce() -> Result<CommandResult>,
+    ) -> Result<CommandResult> {
+        let mut state = self.lock()?;
+        while self.limit.is_some_and(|limit| state.running >= limit) {
+            match self.when_busy {
+                WhenBusy::Ignore => return Ok(CommandResult::KeepOpen),
+                WhenBusy::Queue => {
+                    state = self
+                        .idle
+                        .wait(state)
+                        .map_err(|_| Error::from(ERROR_LOCK_VIOLATION))?;
+                }
+            }
+        }
+        if let (Some(cooldown), Some(last_end)) = (self.cooldown, state.last_end)
+            && last_end.elapsed() < cooldown
+        {
+            return Ok(CommandResult::KeepOpen);
+        }
+        state.running += 1;
+        if state.running == 1 {
+            self.enter_busy(&mut state, base);
+        }
+        drop(state);
+
+        let _running = Running {
+            throttle: self,
+            base,
+        };
+        f()
+    }
+}
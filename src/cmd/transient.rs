@@ -0,0 +1,73 @@
+//! Context items shown on a command item only for a while, e.g. "Undo" or "Cancel".
+
+use std::sync::{Mutex, MutexGuard};
+
+use crate::cmd_item::CommandItem;
+use crate::ctx_item::{CommandContextItem, CommandContextItemBuilder, ContextItem};
+use crate::utils::ComBuilder;
+use windows::Win32::Foundation::ERROR_LOCK_VIOLATION;
+use windows_core::{ComObject, Error, Result};
+
+type Shown<K> = Vec<(K, ComObject<CommandContextItem>)>;
+
+/// Context items added to a target command item and removed again, by key.
+pub(crate) struct TransientItems<K> {
+    target: Option<ComObject<CommandItem>>,
+    shown: Mutex<Shown<K>>,
+}
+
+impl<K: PartialEq> TransientItems<K> {
+    /// Creates items shown on `target`, or nowhere if it is `None`.
+    pub(crate) fn new(target: Option<ComObject<CommandItem>>) -> Self {
+        TransientItems {
+            target,
+            shown: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, Shown<K>>> {
+        self.shown
+            .lock()
+            .map_err(|_| Error::from(ERROR_LOCK_VIOLATION))
+    }
+
+    /// Shows the item built by `item` under `key`, unless one is already shown under it.
+    ///
+    /// `item` is only called if there is a target.
+    pub(crate) fn show<F>(&self, key: K, item: F) -> Result<()>
+    where
+        F: FnOnce() -> Result<ComObject<CommandItem>>,
+    {
+        let Some(target) = &self.target else {
+            return Ok(());
+        };
+        // Keep the list locked while updating the target, so it can't race with `hide`.
+        let mut shown = self.lock()?;
+        if shown.iter().any(|(k, _)| *k == key) {
+            return Ok(());
+        }
+        let item = CommandContextItemBuilder::new(item()?).build();
+        target.more_mut()?.push(ContextItem::Command(item.clone()));
+        shown.push((key, item));
+        Ok(())
+    }
+
+    /// Removes the item shown under `key`, if any.
+    pub(crate) fn hide(&self, key: &K) {
+        let Ok(mut shown) = self.lock() else {
+            return;
+        };
+        let Some(index) = shown.iter().position(|(k, _)| k == key) else {
+            return;
+        };
+        let (_, item) = shown.remove(index);
+        if let Some(target) = &self.target
+            && let Ok(mut more) = target.more_mut()
+        {
+            more.retain(|x| match x {
+                ContextItem::Command(x) => !std::ptr::eq(x.get(), item.get()),
+                _ => true,
+            });
+        }
+    }
+}
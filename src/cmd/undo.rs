@@ -0,0 +1,208 @@
+//! Commands whose action can be undone for a short time.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::cmd_item::{CommandItem, CommandItemBuilder};
+use crate::cmd_result::ToastArgs;
+use crate::icon::{IconData, IconInfo};
+use crate::utils::ComBuilder;
+use windows::Win32::Foundation::ERROR_LOCK_VIOLATION;
+use windows_core::{ComObject, Error, HSTRING, Result};
+
+use super::transient::TransientItems;
+use super::{BaseCommand, BaseCommandBuilder, CommandResult, InvokableCommand};
+
+type ActionBox = Box<dyn Send + Sync + Fn() -> Result<()>>;
+
+/// State shared between an undoable command and its "Undo" command.
+struct UndoState {
+    window: Duration,
+    undo_fn: ActionBox,
+    deadline: Mutex<Option<Instant>>,
+    shown: TransientItems<()>,
+}
+
+impl UndoState {
+    /// Opens the undo window, showing the "Undo" context item on the target.
+    fn arm(self: &Arc<Self>, undo: &ComObject<InvokableCommand>) -> Result<()> {
+        let deadline = Instant::now() + self.window;
+        *self
+            .deadline
+            .lock()
+            .map_err(|_| Error::from(ERROR_LOCK_VIOLATION))? = Some(deadline);
+
+        self.shown.show((), || {
+            Ok(CommandItemBuilder::try_new(undo.to_interface())?.build())
+        })?;
+
+        let state = self.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(state.window);
+            state.expire(deadline);
+        });
+        Ok(())
+    }
+
+    /// Closes the undo window opened at `deadline`, unless it was opened again since.
+    fn expire(&self, deadline: Instant) {
+        if let Ok(mut current) = self.deadline.lock() {
+            if *current != Some(deadline) {
+                return;
+            }
+            *current = None;
+        }
+        self.shown.hide(&());
+    }
+
+    /// Closes the undo window, returning whether it was open.
+    fn take(&self) -> Result<bool> {
+        let open = self
+            .deadline
+            .lock()
+            .map_err(|_| Error::from(ERROR_LOCK_VIOLATION))?
+            .take()
+            .is_some_and(|deadline| Instant::now() < deadline);
+        self.shown.hide(&());
+        Ok(open)
+    }
+}
+
+/// Builder for a command whose action can be undone for a short time.
+///
+/// Invoking the command runs the action, then shows a toast.
+/// Until the undo window elapses, the "Undo" command runs the undo action.
+/// It is shown as a context item of the command item set with [`UndoableCommandBuilder::undo_in`],
+/// and can be placed anywhere else, e.g. in a top-level command item,
+/// with [`UndoableCommandBuilder::build_with_undo`].
+///
+/// Invoking the command again restarts the window, only the latest action can be undone.
+pub struct UndoableCommandBuilder {
+    base: ComObject<BaseCommand>,
+    do_fn: ActionBox,
+    undo_fn: ActionBox,
+    window: Duration,
+    message: HSTRING,
+    result: CommandResult,
+    target: Option<ComObject<CommandItem>>,
+}
+
+impl UndoableCommandBuilder {
+    /// Creates a new builder which runs `do_fn` when invoked, and `undo_fn` to undo it.
+    pub fn new<D, U>(base: ComObject<BaseCommand>, do_fn: D, undo_fn: U) -> Self
+    where
+        D: Send + Sync + Fn() -> Result<()> + 'static,
+        U: Send + Sync + Fn() -> Result<()> + 'static,
+    {
+        UndoableCommandBuilder {
+            base,
+            do_fn: Box::new(do_fn),
+            undo_fn: Box::new(undo_fn),
+            window: Duration::from_secs(10),
+            message: HSTRING::from("Done"),
+            result: CommandResult::KeepOpen,
+            target: None,
+        }
+    }
+
+    /// Sets how long the action can be undone. Defaults to 10 seconds.
+    pub fn window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Sets the message of the toast shown after the action. Defaults to "Done".
+    pub fn message(mut self, message: impl Into<HSTRING>) -> Self {
+        self.message = message.into();
+        self
+    }
+
+    /// Sets the result after the toast. Defaults to [`CommandResult::KeepOpen`],
+    /// so the "Undo" context item stays reachable.
+    pub fn result(mut self, result: CommandResult) -> Self {
+        self.result = result;
+        self
+    }
+
+    /// Shows the "Undo" command as a context item of `item` while the action can be undone.
+    pub fn undo_in(mut self, item: ComObject<CommandItem>) -> Self {
+        self.target = Some(item);
+        self
+    }
+
+    /// Builds the command along with its "Undo" command.
+    ///
+    /// Invoking the "Undo" command outside of the undo window only shows a toast.
+    /// The "Undo" command has the id of the command followed by `.undo`, if the command has an id.
+    pub fn build_with_undo(self) -> (ComObject<InvokableCommand>, ComObject<InvokableCommand>) {
+        let (cmd, undo) = self.build_parts();
+        (cmd.into_object(), undo)
+    }
+
+    fn build_parts(self) -> (InvokableCommand, ComObject<InvokableCommand>) {
+        let state = Arc::new(UndoState {
+            window: self.window,
+            undo_fn: self.undo_fn,
+            deadline: Mutex::new(None),
+            shown: TransientItems::new(self.target),
+        });
+
+        let mut undo_base = BaseCommandBuilder::new()
+            .name("Undo")
+            .icon(IconInfo::new(IconData::from("\u{E7A7}")));
+        if let Ok(id) = self.base.id()
+            && !id.is_empty()
+        {
+            undo_base = undo_base.id(format!("{}.undo", *id));
+        }
+        if let Some(registry) = self.base.registry() {
+            undo_base = undo_base.registry(registry);
+        }
+        let undo_base = undo_base.build();
+        let undo_state = state.clone();
+        let undo: ComObject<InvokableCommand> = InvokableCommand {
+            base: undo_base,
+            func: Box::new(move |_| {
+                if !undo_state.take()? {
+                    return Ok(CommandResult::ShowToast(ToastArgs::new(
+                        "Nothing to undo",
+                        CommandResult::KeepOpen,
+                    )?));
+                }
+                (undo_state.undo_fn)()?;
+                Ok(CommandResult::ShowToast(ToastArgs::new(
+                    "Undone",
+                    CommandResult::KeepOpen,
+                )?))
+            }),
+        }
+        .into_object();
+
+        let do_fn = self.do_fn;
+        let message = self.message;
+        let result = self.result;
+        let armed_undo = undo.clone();
+        let cmd = InvokableCommand {
+            base: self.base,
+            func: Box::new(move |_| {
+                do_fn()?;
+                state.arm(&armed_undo)?;
+                Ok(CommandResult::ShowToast(ToastArgs::new(
+                    message.clone(),
+                    result.clone(),
+                )?))
+            }),
+        };
+        (cmd, undo)
+    }
+}
+
+impl ComBuilder for UndoableCommandBuilder {
+    type Output = InvokableCommand;
+    fn build(self) -> ComObject<InvokableCommand> {
+        self.build_unmanaged().into_object()
+    }
+    fn build_unmanaged(self) -> InvokableCommand {
+        self.build_parts().0
+    }
+}
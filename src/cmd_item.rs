@@ -5,8 +5,9 @@ use crate::icon::IconInfo;
 use crate::notify::*;
 use crate::utils::{ComBuilder, OkOrEmpty, assert_send_sync};
 use crate::{bindings::*, utils::map_array};
+use std::sync::atomic::{AtomicU64, Ordering};
 use windows_core::{
-    AgileReference, ComObject, Event, HSTRING, IInspectable, IUnknownImpl as _, implement,
+    AgileReference, ComObject, HSTRING, IInspectable, IUnknownImpl as _, implement,
 };
 
 /// Represents a command item that can be used in menus and lists.
@@ -21,7 +22,8 @@ pub struct CommandItem {
     title: NotifyLock<HSTRING>,
     subtitle: NotifyLock<HSTRING>,
     more: NotifyLock<Vec<ContextItem>>,
-    event: PropChangedEventHandler,
+    text_generation: AtomicU64,
+    event: TrackedEvent<PropChangedEventHandler>,
 }
 
 /// Field accessors for [`CommandItem`].
@@ -105,7 +107,8 @@ impl ComBuilder for CommandItemBuilder {
             title: NotifyLock::new(title),
             subtitle: NotifyLock::new(subtitle),
             more: NotifyLock::new(self.more),
-            event: Event::new(),
+            text_generation: AtomicU64::new(0),
+            event: TrackedEvent::new(),
         }
     }
 }
@@ -121,6 +124,50 @@ impl CommandItem_Impl {
         self.event.call(|handler| handler.Invoke(&sender, &arg));
     }
 
+    /// Returns a number which changes whenever the title or subtitle is changed,
+    /// or the tags if this is the base of a list item.
+    ///
+    /// Read it before the texts, so a concurrent change can't pair a new text with an old number.
+    pub(crate) fn text_generation(&self) -> u64 {
+        self.text_generation.load(Ordering::Acquire)
+    }
+
+    /// Creates a copy of this item with `command` as its command.
+    ///
+    /// The context items of the copy are shared with this item.
+    pub(crate) fn with_command(
+        &self,
+        command: ICommand,
+    ) -> windows_core::Result<ComObject<CommandItem>> {
+        let more = self
+            .more
+            .read()?
+            .iter()
+            .map(|item| match item {
+                ContextItem::Separator(item) => ContextItem::Separator(item.clone()),
+                ContextItem::Command(item) => ContextItem::Command(item.clone()),
+            })
+            .collect();
+        let mut builder = CommandItemBuilder::try_new(command)?
+            .title(self.title.read()?.clone())
+            .subtitle(self.subtitle.read()?.clone())
+            .more(more);
+        if let Some(icon) = self.icon.read()?.clone() {
+            builder = builder.icon(icon);
+        }
+        Ok(builder.build())
+    }
+
+    /// Changes the number returned by [`CommandItem_Impl::text_generation`].
+    pub(crate) fn bump_text_generation(&self) {
+        self.text_generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    fn text_changed(&self, prop: &str) {
+        self.bump_text_generation();
+        self.emit_self_prop_changed(prop);
+    }
+
     /// Readonly access to [`ICommandItem::Command`].
     ///
     #[doc = include_str!("./bindings_docs/ICommandItem/Command.md")]
@@ -189,7 +236,7 @@ impl CommandItem_Impl {
     ///
     /// Notifies the host about the property change when dropping the guard.
     pub fn title_mut(&self) -> windows_core::Result<NotifyLockWriteGuard<'_, HSTRING>> {
-        self.title.write(|| self.emit_self_prop_changed("Title"))
+        self.title.write(|| self.text_changed("Title"))
     }
 
     /// Readonly access to [`ICommandItem::Subtitle`].
@@ -205,8 +252,7 @@ impl CommandItem_Impl {
     ///
     /// Notifies the host about the property change when dropping the guard.
     pub fn subtitle_mut(&self) -> windows_core::Result<NotifyLockWriteGuard<'_, HSTRING>> {
-        self.subtitle
-            .write(|| self.emit_self_prop_changed("Subtitle"))
+        self.subtitle.write(|| self.text_changed("Subtitle"))
     }
 
     /// Readonly access to [`ICommandItem::MoreCommands`].
@@ -259,6 +305,12 @@ impl ICommandItem_Impl for CommandItem_Impl {
     }
 }
 
+impl Observable for CommandItem {
+    fn is_observed(this: &CommandItem_Impl) -> bool {
+        this.event.has_handlers()
+    }
+}
+
 impl INotifyPropChanged_Impl for CommandItem_Impl {
     fn PropChanged(
         &self,
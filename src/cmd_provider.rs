@@ -1,13 +1,191 @@
 //! Command Provider that provides extension information and commands.
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+
 use crate::bindings::*;
+use crate::cancel::CancellationToken;
+use crate::cmd::{BaseCommand_Impl, base_command};
+use crate::error::catch_panic;
+use crate::host::LogMessage;
 use crate::icon::IconInfo;
-use crate::notify::ItemsChangedEventHandler;
+use crate::notify::{
+    ItemsChangedEventArgs, ItemsChangedEventHandler, NotifyLock, NotifyLockReadGuard,
+    NotifyLockWriteGuard, Observable, TrackedEvent,
+};
+use crate::page::router::PageRouter;
 use crate::utils::{ComBuilder, OkOrEmpty, map_array};
 use windows::Foundation::{IClosable, IClosable_Impl, TypedEventHandler};
-use windows_core::{ComObject, implement};
-use windows_core::{Event, HSTRING, IInspectable};
+use windows::Win32::Foundation::{ERROR_ALREADY_EXISTS, ERROR_LOCK_VIOLATION};
+use windows_core::{ComObject, IUnknownImpl as _, Interface, Result, implement};
+use windows_core::{HSTRING, IInspectable, IUnknown, Weak};
+
+type InitializeHook = Box<dyn Send + Sync + Fn(&IExtensionHost, &CancellationToken) -> Result<()>>;
+type CloseHook = Box<dyn Send + Sync + Fn() -> Result<()>>;
+type DisposeHook = Box<dyn Send + FnOnce() -> Result<()>>;
+
+/// The cancellation token of the current initialization of a provider.
+struct Session {
+    token: CancellationToken,
+    /// Whether the provider wasn't closed since it was initialized.
+    open: bool,
+}
+
+/// Weak references to commands by id, resolving `GetCommand` for a [`CommandProvider`].
+///
+/// Share a registry between a provider and its commands with [`CommandProviderBuilder::registry`]
+/// and [`BaseCommandBuilder::registry`][`crate::cmd::BaseCommandBuilder::registry`]:
+/// commands whose base command has the registry are registered when they are built,
+/// wherever they are reachable from. Clones share the same commands.
+///
+/// Commands sharing a base command are the same command to the registry,
+/// the one built or registered last is resolved, e.g. a page rather than the list page it wraps.
+#[derive(Clone, Default)]
+pub struct CommandRegistry {
+    inner: Arc<RegistryState>,
+}
+
+#[derive(Default)]
+struct RegistryState {
+    commands: RwLock<HashMap<HSTRING, RegistryEntry>>,
+    /// Ids which were looked up and not found, so the provider doesn't search its items again.
+    misses: Mutex<HashSet<HSTRING>>,
+}
+
+struct RegistryEntry {
+    command: Weak<ICommand>,
+    /// Address of the base command of the command, if it is a command of this crate.
+    base: Option<usize>,
+}
+
+fn same_object(a: &ICommand, b: &ICommand) -> bool {
+    match (a.cast::<IUnknown>(), b.cast::<IUnknown>()) {
+        (Ok(a), Ok(b)) => a.as_raw() == b.as_raw(),
+        _ => false,
+    }
+}
+
+fn base_key(command: &ICommand) -> Option<usize> {
+    base_command(command).map(|base| &*base as *const BaseCommand_Impl as usize)
+}
+
+impl CommandRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `command` under its id, if it has one.
+    ///
+    /// Only a weak reference is kept, the command must be kept alive elsewhere.
+    /// Duplicate ids are caught in debug builds: registering a different live command under the same id
+    /// fails with `ERROR_ALREADY_EXISTS` there, unless both share their base command.
+    /// Release builds replace the registered command instead.
+    pub fn register(&self, command: &ICommand) -> Result<()> {
+        let id = command.Id()?;
+        if id.is_empty() {
+            return Ok(());
+        }
+        let base = base_key(command);
+        let mut commands = self
+            .inner
+            .commands
+            .write()
+            .map_err(|_| windows_core::Error::from(ERROR_LOCK_VIOLATION))?;
+        if let Some(existing) = commands.get(&id)
+            && let Some(live) = existing.command.upgrade()
+            && live.Id().is_ok_and(|live_id| live_id == id)
+        {
+            if same_object(&live, command) {
+                return Ok(());
+            }
+            if cfg!(debug_assertions) && (existing.base.is_none() || existing.base != base) {
+                return Err(windows_core::Error::new(
+                    ERROR_ALREADY_EXISTS.to_hresult(),
+                    format!("Another command is registered with id {}", id),
+                ));
+            }
+        }
+        if let Ok(mut misses) = self.inner.misses.lock() {
+            misses.remove(&id);
+        }
+        commands.insert(
+            id,
+            RegistryEntry {
+                command: command.downgrade()?,
+                base,
+            },
+        );
+        Ok(())
+    }
+
+    /// Registers the command of `item` and the commands of its context items,
+    /// skipping commands which can't be registered.
+    fn register_item(&self, item: &ICommandItem) {
+        if let Ok(command) = item.Command() {
+            let _ = self.register(&command);
+        }
+        if let Ok(more) = item.MoreCommands() {
+            for context_item in more.iter().flatten() {
+                if let Ok(context_item) = context_item.cast::<ICommandItem>() {
+                    self.register_item(&context_item);
+                }
+            }
+        }
+    }
+
+    /// Returns the registered command with `id`, if it is still alive and still has that id.
+    pub fn get(&self, id: &HSTRING) -> Option<ICommand> {
+        let command = self
+            .inner
+            .commands
+            .read()
+            .ok()?
+            .get(id)?
+            .command
+            .upgrade()?;
+        command
+            .Id()
+            .is_ok_and(|live_id| live_id == *id)
+            .then_some(command)
+    }
+
+    fn is_miss(&self, id: &HSTRING) -> bool {
+        self.inner
+            .misses
+            .lock()
+            .is_ok_and(|misses| misses.contains(id))
+    }
+
+    fn add_miss(&self, id: &HSTRING) {
+        if let Ok(mut misses) = self.inner.misses.lock() {
+            misses.insert(id.clone());
+        }
+    }
+
+    /// Forgets the ids which were not found, e.g. after a command changed its id.
+    pub(crate) fn forget_misses(&self) {
+        if let Ok(mut misses) = self.inner.misses.lock() {
+            misses.clear();
+        }
+    }
+}
 
 /// Command Provider that provides extension information and commands.
+///
+/// Commands with an id are registered in the [`CommandRegistry`] of the provider, so Command Palette can resolve them
+/// with `GetCommand`, e.g. for deep links or [`GoToPageArgs`][`crate::cmd_result::GoToPageArgs`] results.
+/// Commands built with the registry of the provider are registered when they are built.
+/// The commands of top-level and fallback items, and of their context items, are registered too.
+/// Other commands, e.g. pages only reachable by id, are registered with
+/// [`CommandProviderBuilder::add_command`] or [`CommandProvider_Impl::register_command`].
+///
+/// Ids which aren't registered are resolved by the [`PageRouter`] of the provider, if any,
+/// see [`CommandProviderBuilder::router`].
+///
+/// Resources of the extension are set up and released by the hooks set with
+/// [`CommandProviderBuilder::on_initialize`], [`CommandProviderBuilder::on_close`]
+/// and [`CommandProviderBuilder::on_dispose`].
+/// Background work should stop once the [`CancellationToken`] of the provider is cancelled, when it is closed.
 /// 
 #[doc = include_str!("./bindings_docs/ICommandProvider.md")]
 #[implement(ICommandProvider, IClosable, INotifyItemsChanged)]
@@ -17,9 +195,16 @@ pub struct CommandProvider {
     icon: Option<ComObject<IconInfo>>,
     settings: Option<ICommandSettings>,
     frozen: bool,
-    top_level: Vec<ICommandItem>,
-    fallbacks: Vec<IFallbackCommandItem>,
-    event: ItemsChangedEventHandler,
+    top_level: NotifyLock<Vec<ICommandItem>>,
+    fallbacks: NotifyLock<Vec<IFallbackCommandItem>>,
+    commands: Vec<ICommand>,
+    registry: CommandRegistry,
+    router: Option<PageRouter>,
+    on_initialize: Option<InitializeHook>,
+    on_close: Option<CloseHook>,
+    on_dispose: Mutex<Option<DisposeHook>>,
+    session: Mutex<Session>,
+    event: TrackedEvent<ItemsChangedEventHandler>,
 }
 
 /// Builder for [`CommandProvider`].
@@ -31,6 +216,12 @@ pub struct CommandProviderBuilder {
     frozen: bool,
     top_level: Vec<ICommandItem>,
     fallbacks: Vec<IFallbackCommandItem>,
+    commands: Vec<ICommand>,
+    registry: CommandRegistry,
+    router: Option<PageRouter>,
+    on_initialize: Option<InitializeHook>,
+    on_close: Option<CloseHook>,
+    on_dispose: Option<DisposeHook>,
 }
 
 impl CommandProviderBuilder {
@@ -44,6 +235,12 @@ impl CommandProviderBuilder {
             frozen: false,
             top_level: Vec::new(),
             fallbacks: Vec::new(),
+            commands: Vec::new(),
+            registry: CommandRegistry::new(),
+            router: None,
+            on_initialize: None,
+            on_close: None,
+            on_dispose: None,
         }
     }
 
@@ -106,20 +303,106 @@ impl CommandProviderBuilder {
         self.fallbacks.push(item);
         self
     }
+
+    /// Adds a command which is only reachable by its id, e.g. a page opened with a `GoToPage` result.
+    ///
+    /// The provider keeps the command alive.
+    /// Commands of top-level and fallback items are registered without this.
+    pub fn add_command(mut self, command: ICommand) -> Self {
+        self.commands.push(command);
+        self
+    }
+
+    /// Sets the registry resolving the commands of the provider.
+    ///
+    /// Build commands with the same registry, see [`CommandRegistry`].
+    /// Defaults to a registry of its own.
+    pub fn registry(mut self, registry: CommandRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// Sets the router building pages on demand for ids which aren't registered.
+    ///
+    /// Navigate to its pages with [`CommandResult::go_to`][`crate::cmd::CommandResult::go_to`].
+    pub fn router(mut self, router: PageRouter) -> Self {
+        self.router = Some(router);
+        self
+    }
+
+    /// Sets the hook called when Command Palette initializes the provider with its host.
+    ///
+    /// The hook receives the cancellation token of the provider, to hand it to background work it starts.
+    /// If the provider is initialized again after it was closed, the hook receives a new token.
+    /// An error fails the initialization.
+    pub fn on_initialize<F>(mut self, hook: F) -> Self
+    where
+        F: Send + Sync + Fn(&IExtensionHost, &CancellationToken) -> Result<()> + 'static,
+    {
+        self.on_initialize = Some(Box::new(hook));
+        self
+    }
+
+    /// Sets the hook called when Command Palette closes the provider,
+    /// after its cancellation token is cancelled.
+    ///
+    /// The hook is called once per initialization, closing the provider again does nothing
+    /// until it is initialized again.
+    pub fn on_close<F>(mut self, hook: F) -> Self
+    where
+        F: Send + Sync + Fn() -> Result<()> + 'static,
+    {
+        self.on_close = Some(Box::new(hook));
+        self
+    }
+
+    /// Sets the hook called once when Command Palette disposes the extension of the provider,
+    /// after the provider is closed.
+    pub fn on_dispose<F>(mut self, hook: F) -> Self
+    where
+        F: Send + FnOnce() -> Result<()> + 'static,
+    {
+        self.on_dispose = Some(Box::new(hook));
+        self
+    }
 }
 
 impl ComBuilder for CommandProviderBuilder {
     type Output = CommandProvider;
     fn build_unmanaged(self) -> CommandProvider {
+        let registry = self.registry;
+        for command in self.commands.iter() {
+            if let Err(e) = registry.register(command) {
+                LogMessage::warning(e.message().into()).log();
+            }
+        }
+        for item in self.top_level.iter() {
+            registry.register_item(item);
+        }
+        for item in self.fallbacks.iter() {
+            if let Ok(item) = item.cast() {
+                registry.register_item(&item);
+            }
+        }
         CommandProvider {
             id: self.id,
             display_name: self.display_name,
             icon: self.icon,
             settings: self.settings,
             frozen: self.frozen,
-            top_level: self.top_level,
-            fallbacks: self.fallbacks,
-            event: Event::new(),
+            top_level: NotifyLock::new(self.top_level),
+            fallbacks: NotifyLock::new(self.fallbacks),
+            commands: self.commands,
+            registry,
+            router: self.router,
+            on_initialize: self.on_initialize,
+            on_close: self.on_close,
+            on_dispose: Mutex::new(self.on_dispose),
+            session: Mutex::new(Session {
+                token: CancellationToken::new(),
+                open: true,
+            }),
+            event: TrackedEvent::new(),
         }
     }
 }
@@ -130,6 +413,173 @@ impl Default for CommandProviderBuilder {
     }
 }
 
+impl CommandProvider_Impl {
+    fn emit_items_changed(&self, total_items: i32) {
+        let sender: IInspectable = self.to_interface();
+        let args: IItemsChangedEventArgs = ItemsChangedEventArgs(total_items).into();
+        self.event.call(|handler| handler.Invoke(&sender, &args));
+    }
+
+    /// Readonly access to [`ICommandProvider::TopLevelCommands`].
+    ///
+    #[doc = include_str!("./bindings_docs/ICommandProvider/TopLevelCommands.md")]
+    pub fn top_level(&self) -> Result<NotifyLockReadGuard<'_, Vec<ICommandItem>>> {
+        self.top_level.read()
+    }
+
+    /// Mutable access to [`ICommandProvider::TopLevelCommands`].
+    ///
+    #[doc = include_str!("./bindings_docs/ICommandProvider/TopLevelCommands.md")]
+    ///
+    /// Notifies the host about the change when dropping the guard.
+    /// Commands of added items are registered, so they can be resolved with `GetCommand`.
+    pub fn top_level_mut(&self) -> Result<NotifyLockWriteGuard<'_, Vec<ICommandItem>, usize>> {
+        self.top_level.write_with_peek(
+            |v| {
+                for item in v.iter() {
+                    self.registry.register_item(item);
+                }
+                self.registry.forget_misses();
+                v.len()
+            },
+            |len| self.emit_items_changed(len as i32),
+        )
+    }
+
+    /// Readonly access to [`ICommandProvider::FallbackCommands`].
+    ///
+    #[doc = include_str!("./bindings_docs/ICommandProvider/FallbackCommands.md")]
+    pub fn fallbacks(&self) -> Result<NotifyLockReadGuard<'_, Vec<IFallbackCommandItem>>> {
+        self.fallbacks.read()
+    }
+
+    /// Mutable access to [`ICommandProvider::FallbackCommands`].
+    ///
+    #[doc = include_str!("./bindings_docs/ICommandProvider/FallbackCommands.md")]
+    ///
+    /// Notifies the host about the change when dropping the guard.
+    /// Commands of added items are registered, so they can be resolved with `GetCommand`.
+    pub fn fallbacks_mut(
+        &self,
+    ) -> Result<NotifyLockWriteGuard<'_, Vec<IFallbackCommandItem>, usize>> {
+        self.fallbacks.write_with_peek(
+            |v| {
+                for item in v.iter() {
+                    if let Ok(item) = item.cast() {
+                        self.registry.register_item(&item);
+                    }
+                }
+                self.registry.forget_misses();
+                v.len()
+            },
+            |len| self.emit_items_changed(len as i32),
+        )
+    }
+
+    /// Registers `command` under its id, so it can be resolved with `GetCommand`.
+    ///
+    /// See [`CommandRegistry::register`].
+    pub fn register_command(&self, command: &ICommand) -> Result<()> {
+        self.registry.register(command)
+    }
+
+    /// Returns the registry of the provider.
+    pub fn registry(&self) -> &CommandRegistry {
+        &self.registry
+    }
+
+    /// Returns the registered command with `id`, if it is still alive.
+    ///
+    /// If `id` is not registered, the commands of the provider and its items are searched once,
+    /// so commands added to its items or renamed after the provider was built are found too.
+    /// Ids which aren't found are remembered until the items of the provider or the id of a command change.
+    /// Then, the page of `id` is built by the router, if any route matches.
+    pub fn command(&self, id: &HSTRING) -> Option<ICommand> {
+        if let Some(command) = self.registry.get(id) {
+            return Some(command);
+        }
+        if !self.registry.is_miss(id) {
+            for command in self.commands.iter() {
+                let _ = self.registry.register(command);
+            }
+            if let Ok(top_level) = self.top_level.read() {
+                for item in top_level.iter() {
+                    self.registry.register_item(item);
+                }
+            }
+            if let Ok(fallbacks) = self.fallbacks.read() {
+                for item in fallbacks.iter() {
+                    if let Ok(item) = item.cast() {
+                        self.registry.register_item(&item);
+                    }
+                }
+            }
+            if let Some(command) = self.registry.get(id) {
+                return Some(command);
+            }
+            self.registry.add_miss(id);
+        }
+        self.router.as_ref()?.resolve(id).ok()
+    }
+}
+
+impl CommandProvider_Impl {
+    /// Returns the cancellation token of the provider, which is cancelled when the provider is closed.
+    ///
+    /// A provider initialized again after it was closed gets a new token,
+    /// tokens returned before stay cancelled.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.session
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .token
+            .clone()
+    }
+
+    /// Opens a new session, returning its token, which replaces the current one if it was cancelled.
+    fn renew_token(&self) -> CancellationToken {
+        let mut session = self.session.lock().unwrap_or_else(PoisonError::into_inner);
+        if session.token.is_cancelled() {
+            session.token = CancellationToken::new();
+        }
+        session.open = true;
+        session.token.clone()
+    }
+
+    /// Closes the provider, cancelling its token and calling the close hook.
+    ///
+    /// The close hook is only called on the first call after each initialization.
+    pub fn close(&self) -> Result<()> {
+        let (token, open) = {
+            let mut session = self.session.lock().unwrap_or_else(PoisonError::into_inner);
+            let open = std::mem::replace(&mut session.open, false);
+            (session.token.clone(), open)
+        };
+        // Cancel outside the lock, callbacks of the token may ask for it again.
+        token.cancel();
+        match &self.on_close {
+            Some(hook) if open => catch_panic(hook),
+            _ => Ok(()),
+        }
+    }
+
+    /// Disposes the provider, closing it first and then calling the dispose hook.
+    ///
+    /// Only the first call has an effect.
+    pub fn dispose(&self) -> Result<()> {
+        let closed = self.close();
+        let hook = self
+            .on_dispose
+            .lock()
+            .map_err(|_| windows_core::Error::from(ERROR_LOCK_VIOLATION))?
+            .take();
+        match hook {
+            Some(hook) => catch_panic(hook).and(closed),
+            None => closed,
+        }
+    }
+}
+
 impl ICommandProvider_Impl for CommandProvider_Impl {
     fn Id(&self) -> windows_core::Result<windows_core::HSTRING> {
         Ok(self.id.clone())
@@ -155,29 +605,40 @@ impl ICommandProvider_Impl for CommandProvider_Impl {
     }
 
     fn TopLevelCommands(&self) -> windows_core::Result<windows_core::Array<ICommandItem>> {
-        Ok(map_array(&self.top_level, |x| x.clone().into()))
+        Ok(map_array(&self.top_level.read()?, |x| x.clone().into()))
     }
 
     fn FallbackCommands(&self) -> windows_core::Result<windows_core::Array<IFallbackCommandItem>> {
-        Ok(map_array(&self.fallbacks, |x| x.clone().into()))
+        Ok(map_array(&self.fallbacks.read()?, |x| x.clone().into()))
     }
 
-    fn GetCommand(&self, _: &windows_core::HSTRING) -> windows_core::Result<ICommand> {
-        Err(windows_core::Error::empty())
+    fn GetCommand(&self, id: &windows_core::HSTRING) -> windows_core::Result<ICommand> {
+        catch_panic(|| self.command(id).ok_or_empty())
     }
 
     fn InitializeWithHost(
         &self,
         host: windows_core::Ref<'_, IExtensionHost>,
     ) -> windows_core::Result<()> {
-        crate::host::set_ext_host(host.ok()?);
-        Ok(())
+        let host = host.ok()?;
+        crate::host::set_ext_host(host);
+        let token = self.renew_token();
+        match &self.on_initialize {
+            Some(hook) => catch_panic(|| hook(host, &token)),
+            None => Ok(()),
+        }
     }
 }
 
 impl IClosable_Impl for CommandProvider_Impl {
     fn Close(&self) -> windows_core::Result<()> {
-        Ok(())
+        self.close()
+    }
+}
+
+impl Observable for CommandProvider {
+    fn is_observed(this: &CommandProvider_Impl) -> bool {
+        this.event.has_handlers()
     }
 }
 
@@ -194,3 +655,57 @@ impl INotifyItemsChanged_Impl for CommandProvider_Impl {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::BaseCommandBuilder;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn registers_built_commands_and_catches_duplicates() {
+        let registry = CommandRegistry::new();
+        let first = BaseCommandBuilder::new()
+            .id("dup")
+            .registry(&registry)
+            .build();
+        let first: ICommand = first.to_interface();
+        assert_eq!(registry.get(&"dup".into()), Some(first.clone()));
+
+        let second: ICommand = BaseCommandBuilder::new().id("dup").build().to_interface();
+        let registered = registry.register(&second);
+        if cfg!(debug_assertions) {
+            let error = registered.unwrap_err();
+            assert_eq!(error.code(), ERROR_ALREADY_EXISTS.to_hresult());
+            assert_eq!(registry.get(&"dup".into()), Some(first));
+        } else {
+            registered.unwrap();
+            assert_eq!(registry.get(&"dup".into()), Some(second));
+        }
+    }
+
+    #[test]
+    fn close_hook_runs_once_per_initialization() {
+        let closed = Arc::new(AtomicUsize::new(0));
+        let provider = CommandProviderBuilder::new()
+            .on_close({
+                let closed = closed.clone();
+                move || {
+                    closed.fetch_add(1, Ordering::Relaxed);
+                    Ok(())
+                }
+            })
+            .build();
+        let first = provider.cancellation_token();
+        provider.close().unwrap();
+        provider.close().unwrap();
+        assert_eq!(closed.load(Ordering::Relaxed), 1);
+        assert!(first.is_cancelled());
+
+        let second = provider.renew_token();
+        assert!(!second.is_cancelled());
+        provider.close().unwrap();
+        assert_eq!(closed.load(Ordering::Relaxed), 2);
+        assert!(second.is_cancelled());
+    }
+}
@@ -6,6 +6,7 @@ use crate::{
         ICommandResultArgs_Impl, IConfirmationArgs, IConfirmationArgs_Impl, IGoToPageArgs,
         IGoToPageArgs_Impl, IToastArgs, IToastArgs_Impl,
     },
+    page::router::Route,
     utils::ComBuilder,
 };
 use windows::Win32::Foundation::ERROR_BAD_ARGUMENTS;
@@ -38,6 +39,18 @@ pub enum CommandResult {
     Confirm(ComObject<ConfirmationArgs>),
 }
 
+impl CommandResult {
+    /// Navigates to the page of `route`, pushing it onto the navigation stack.
+    ///
+    /// The page is resolved by the [`PageRouter`][`crate::page::router::PageRouter`] of the provider.
+    pub fn go_to(route: &Route) -> Result<Self> {
+        Ok(CommandResult::GoToPage(GoToPageArgs::new(
+            NavigationMode::Push,
+            route.path(),
+        )?))
+    }
+}
+
 /// A convenience wrapper for [`CommandResult`]
 #[implement(ICommandResult)]
 pub(crate) struct CommandResultStruct(CommandResult);
@@ -1,9 +1,10 @@
 //! Form content that can be used to accept user input.
 use crate::bindings::*;
 use crate::cmd::CommandResult;
+use crate::error::{BoxError, ErrorPolicy, catch_panic, into_win_error};
 use crate::notify::*;
 use crate::utils::{ComBuilder, assert_send_sync};
-use windows_core::{ComObject, Event, HSTRING, IInspectable, IUnknownImpl as _, implement};
+use windows_core::{ComObject, HSTRING, IInspectable, IUnknownImpl as _, implement};
 
 pub type SubmitBox = Box<
     dyn Send
@@ -22,7 +23,7 @@ pub struct FormContent {
     data_json: NotifyLock<HSTRING>,
     state_json: NotifyLock<HSTRING>,
     submit: SubmitBox,
-    event: PropChangedEventHandler,
+    event: TrackedEvent<PropChangedEventHandler>,
 }
 
 /// Builder for [`FormContent`].
@@ -31,6 +32,7 @@ pub struct FormContentBuilder {
     data_json: HSTRING,
     state_json: HSTRING,
     submit: SubmitBox,
+    error_policy: Option<ErrorPolicy>,
 }
 
 impl FormContentBuilder {
@@ -41,6 +43,7 @@ impl FormContentBuilder {
             data_json: HSTRING::default(),
             state_json: HSTRING::default(),
             submit: Box::new(|_, _, _| Ok(CommandResult::KeepOpen)),
+            error_policy: None,
         }
     }
 
@@ -90,6 +93,29 @@ impl FormContentBuilder {
         self.submit = Box::new(submit);
         self
     }
+
+    /// Like [`FormContentBuilder::submit`], but the submit handler may return any error,
+    /// e.g. `anyhow::Error`, converted with [`into_win_error`].
+    pub fn submit_any<F, E>(mut self, submit: F) -> Self
+    where
+        F: Send
+            + Sync
+            + Fn(&FormContent_Impl, &HSTRING, &HSTRING) -> Result<CommandResult, E>
+            + 'static,
+        E: Into<BoxError>,
+    {
+        self.submit =
+            Box::new(move |form, inputs, data| submit(form, inputs, data).map_err(into_win_error));
+        self
+    }
+
+    /// Sets how errors of the submit handler are reported to the user.
+    ///
+    /// By default, errors are returned to Command Palette without feedback to the user.
+    pub fn error_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.error_policy = Some(policy);
+        self
+    }
 }
 
 impl ComBuilder for FormContentBuilder {
@@ -99,8 +125,16 @@ impl ComBuilder for FormContentBuilder {
             template_json: NotifyLock::new(self.template_json),
             data_json: NotifyLock::new(self.data_json),
             state_json: NotifyLock::new(self.state_json),
-            submit: self.submit,
-            event: Event::new(),
+            submit: match self.error_policy {
+                Some(policy) => {
+                    let submit = self.submit;
+                    Box::new(move |form, inputs, data| {
+                        policy.handle_result(catch_panic(|| submit(form, inputs, data)))
+                    })
+                }
+                None => self.submit,
+            },
+            event: TrackedEvent::new(),
         }
     }
 }
@@ -158,11 +192,17 @@ impl IFormContent_Impl for FormContent_Impl {
         inputs: &windows_core::HSTRING,
         data: &windows_core::HSTRING,
     ) -> windows_core::Result<ICommandResult> {
-        (self.submit)(self, inputs, data).map(|x| x.into())
+        catch_panic(|| (self.submit)(self, inputs, data)).map(|x| x.into())
     }
 }
 
 impl IContent_Impl for FormContent_Impl {}
+impl Observable for FormContent {
+    fn is_observed(this: &FormContent_Impl) -> bool {
+        this.event.has_handlers()
+    }
+}
+
 impl INotifyPropChanged_Impl for FormContent_Impl {
     fn PropChanged(
         &self,
@@ -3,7 +3,7 @@
 use crate::bindings::*;
 use crate::notify::*;
 use crate::utils::assert_send_sync;
-use windows_core::{Event, HSTRING, IInspectable, IUnknownImpl as _, implement};
+use windows_core::{HSTRING, IInspectable, IUnknownImpl as _, implement};
 use windows_core::ComObject;
 
 /// Markdown content that can be used to display formatted text.
@@ -14,14 +14,14 @@ use windows_core::ComObject;
 #[implement(IMarkdownContent, IContent, INotifyPropChanged)]
 pub struct MarkdownContent {
     body: NotifyLock<HSTRING>,
-    event: PropChangedEventHandler,
+    event: TrackedEvent<PropChangedEventHandler>,
 }
 
 impl MarkdownContent {
     /// Creates an unmanaged instance of `MarkdownContent` with the specified body.
     pub fn new_unmanaged(body: impl Into<HSTRING>) -> Self {
         let body = NotifyLock::new(body.into());
-        let event = Event::new();
+        let event = TrackedEvent::new();
         MarkdownContent { body, event }
     }
 
@@ -63,6 +63,12 @@ impl IMarkdownContent_Impl for MarkdownContent_Impl {
 }
 
 impl IContent_Impl for MarkdownContent_Impl {}
+impl Observable for MarkdownContent {
+    fn is_observed(this: &MarkdownContent_Impl) -> bool {
+        this.event.has_handlers()
+    }
+}
+
 impl INotifyPropChanged_Impl for MarkdownContent_Impl {
     fn PropChanged(
         &self,
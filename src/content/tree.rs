@@ -2,9 +2,9 @@
 
 use super::Content;
 use crate::notify::*;
-use crate::utils::{ComBuilder, assert_send_sync};
-use crate::{bindings::*, utils::map_array};
-use windows_core::{Event, IInspectable, IUnknownImpl as _, Result, implement};
+use crate::bindings::*;
+use crate::utils::{ArrayCache, ComBuilder, assert_send_sync};
+use windows_core::{IInspectable, IUnknownImpl as _, Result, implement};
 use windows_core::ComObject;
 
 /// Tree content that can be used to display nested content.
@@ -16,8 +16,9 @@ use windows_core::ComObject;
 pub struct TreeContent {
     root: NotifyLock<Content>,
     children: NotifyLock<Vec<Content>>,
-    prop_event: PropChangedEventHandler,
-    item_event: ItemsChangedEventHandler,
+    children_cache: ArrayCache<IContent, Content>,
+    prop_event: TrackedEvent<PropChangedEventHandler>,
+    item_event: TrackedEvent<ItemsChangedEventHandler>,
 }
 
 /// Builder for [`TreeContent`].
@@ -54,8 +55,9 @@ impl ComBuilder for TreeContentBuilder {
         TreeContent {
             root: NotifyLock::new(self.root),
             children: NotifyLock::new(self.children),
-            prop_event: Event::new(),
-            item_event: Event::new(),
+            children_cache: ArrayCache::new(),
+            prop_event: TrackedEvent::new(),
+            item_event: TrackedEvent::new(),
         }
     }
 }
@@ -105,8 +107,13 @@ impl TreeContent_Impl {
     ///
     /// Notifies the host about the property change when dropping the guard.
     pub fn children_mut(&self) -> Result<NotifyLockWriteGuard<'_, Vec<Content>, usize>> {
-        self.children
-            .write_with_peek(|v| v.len(), |len| self.emit_self_items_changed(len as i32))
+        self.children.write_with_peek(
+            |v| {
+                self.children_cache.invalidate();
+                v.len()
+            },
+            |len| self.emit_self_items_changed(len as i32),
+        )
     }
 }
 
@@ -117,12 +124,20 @@ impl ITreeContent_Impl for TreeContent_Impl {
 
     fn GetChildren(&self) -> windows_core::Result<windows_core::Array<IContent>> {
         let children = self.children.read()?;
-        Ok(map_array(&children, |x| Some(x.into())))
+        Ok(self
+            .children_cache
+            .get_or_init(|| children.iter(), |x| Some(x.into())))
     }
 }
 
 impl IContent_Impl for TreeContent_Impl {}
 
+impl Observable for TreeContent {
+    fn is_observed(this: &TreeContent_Impl) -> bool {
+        this.prop_event.has_handlers() || this.item_event.has_handlers()
+    }
+}
+
 impl INotifyPropChanged_Impl for TreeContent_Impl {
     fn PropChanged(
         &self,
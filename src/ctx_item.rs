@@ -2,10 +2,11 @@
 
 use std::ops::Deref;
 
+use crate::cmd::{InvokableCommand, composite::ConfirmedCommandBuilder};
 use crate::cmd_item::{CommandItem, CommandItem_Impl};
 use crate::utils::{ComBuilder, OkOrEmpty, assert_send_sync};
 use crate::{bindings::*, notify::*};
-use windows_core::{ComObject, IInspectable, IUnknownImpl as _, Result, implement};
+use windows_core::{ComObject, HSTRING, IInspectable, IUnknownImpl as _, Result, implement};
 
 /// Represents a separator in the context menu.
 ///
@@ -39,6 +40,7 @@ pub struct CommandContextItemBuilder {
     base: ComObject<CommandItem>,
     critical: bool,
     shortcut: Option<KeyChord>,
+    confirm: Option<HSTRING>,
 }
 
 impl CommandContextItemBuilder {
@@ -48,6 +50,7 @@ impl CommandContextItemBuilder {
             base,
             critical: false,
             shortcut: None,
+            confirm: None,
         }
     }
 
@@ -57,6 +60,18 @@ impl CommandContextItemBuilder {
         self
     }
 
+    /// Asks the user for confirmation with `description` before invoking the command, if the item is critical.
+    ///
+    /// When building a critical item whose command is an [`InvokableCommand`],
+    /// the context item gets a copy of the command item whose command is wrapped with [`ConfirmedCommandBuilder`],
+    /// titled with the name of the command. The command item passed to [`CommandContextItemBuilder::new`]
+    /// is left unchanged, so it can be shared with other items.
+    /// Other commands, e.g. pages, are not wrapped.
+    pub fn confirm(mut self, description: impl Into<HSTRING>) -> Self {
+        self.confirm = Some(description.into());
+        self
+    }
+
     /// Sets the keyboard shortcut for the command.
     pub fn shortcut(mut self, shortcut: KeyChord) -> Self {
         self.shortcut = Some(shortcut);
@@ -67,8 +82,22 @@ impl CommandContextItemBuilder {
 impl ComBuilder for CommandContextItemBuilder {
     type Output = CommandContextItem;
     fn build_unmanaged(self) -> CommandContextItem {
+        let mut base = self.base;
+        if self.critical
+            && let Some(description) = self.confirm
+            && let Ok(command) = base.command().and_then(|c| c.resolve())
+            && let Ok(inner) = ComObject::<InvokableCommand>::cast_from(&command)
+        {
+            let confirmed = ConfirmedCommandBuilder::new(inner)
+                .description(description)
+                .critical(true)
+                .build();
+            if let Ok(item) = base.with_command(confirmed.to_interface()) {
+                base = item;
+            }
+        }
         CommandContextItem {
-            base: self.base,
+            base,
             critical: NotifyLock::new(self.critical),
             shortcut: NotifyLock::new(self.shortcut),
         }
@@ -0,0 +1,230 @@
+//! Reporting errors of callbacks to the user.
+//!
+//! By default, an `Err` returned by an invoke, submit, update or query callback
+//! goes back to Command Palette as a bare `HRESULT`, and the user gets no feedback.
+//! An [`ErrorPolicy`] set on the builder of the callback shows the error as a toast or a status message,
+//! and logs it through [`LogMessage`], instead of returning it.
+//!
+//! Panics of callbacks called by Command Palette are caught before they unwind across the COM boundary,
+//! which would abort the extension process. They are returned as `E_UNEXPECTED` errors and logged,
+//! and optionally shown as status messages, see [`show_panics`].
+//!
+//! Callbacks may also return other error types, like `anyhow::Error` or any [`std::error::Error`],
+//! through the `*_any` variants of the builder methods, e.g.
+//! [`InvokableCommandBuilder::func_any`][`crate::cmd::InvokableCommandBuilder::func_any`].
+
+use std::any::Any;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::cmd_result::{CommandResult, ToastArgs};
+use crate::host::{LogMessage, MessageState, StatusContext, StatusMessageBuilder, show_status_for};
+use windows::Win32::Foundation::{E_FAIL, E_UNEXPECTED};
+use windows_core::{Error, HSTRING, Result};
+
+/// Any error which can be converted into a [`windows_core::Error`] with [`into_win_error`].
+///
+/// `anyhow::Error` and every [`std::error::Error`] convert into it.
+pub type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// Converts any error into a [`windows_core::Error`].
+///
+/// [`windows_core::Error`]s are returned unchanged, other errors become `E_FAIL` with their message.
+pub fn into_win_error(error: impl Into<BoxError>) -> Error {
+    match error.into().downcast::<Error>() {
+        Ok(error) => *error,
+        Err(error) => Error::new(E_FAIL, error.to_string()),
+    }
+}
+
+static SHOW_PANICS: AtomicBool = AtomicBool::new(false);
+
+/// Sets whether panics of callbacks are shown to the user as error status messages.
+///
+/// Panics are always logged through [`LogMessage`]. Disabled by default.
+pub fn show_panics(show: bool) {
+    SHOW_PANICS.store(show, Ordering::Relaxed);
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    }
+}
+
+/// Runs a callback called by Command Palette, turning a panic into an `E_UNEXPECTED` error.
+///
+/// The panic is logged, and shown as an error status message if enabled with [`show_panics`].
+pub(crate) fn catch_panic<T>(f: impl FnOnce() -> Result<T>) -> Result<T> {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let message = HSTRING::from(format!("Extension panicked: {}", panic_message(&*payload)));
+        LogMessage::error(message.clone()).log();
+        if SHOW_PANICS.load(Ordering::Relaxed) {
+            let status = StatusMessageBuilder::new()
+                .state(MessageState::Error)
+                .message(message.clone())
+                .build();
+            show_status_for(
+                status.into(),
+                StatusContext::Extension,
+                Duration::from_secs(8),
+            );
+        }
+        Err(Error::new(E_UNEXPECTED, message.to_string_lossy()))
+    })
+}
+
+/// How errors are shown to the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorDisplay {
+    /// Return the error to Command Palette, without showing it.
+    #[default]
+    Return,
+    /// Show the error as a toast.
+    ///
+    /// Callbacks which can't return a toast, like search text updates, show a status message instead.
+    Toast,
+    /// Show the error as a status message with [`MessageState::Error`].
+    Status,
+}
+
+/// How errors of a callback are reported.
+///
+/// The default policy returns errors to Command Palette unchanged.
+#[derive(Debug, Clone)]
+pub struct ErrorPolicy {
+    display: ErrorDisplay,
+    log: bool,
+    duration: Duration,
+    context: Option<HSTRING>,
+}
+
+impl ErrorPolicy {
+    /// Creates a policy which returns errors to Command Palette without showing or logging them.
+    pub fn new() -> Self {
+        ErrorPolicy {
+            display: ErrorDisplay::Return,
+            log: false,
+            duration: Duration::from_secs(8),
+            context: None,
+        }
+    }
+
+    /// Creates a policy which shows errors as toasts and logs them.
+    pub fn toast() -> Self {
+        Self::new().display(ErrorDisplay::Toast).log(true)
+    }
+
+    /// Creates a policy which shows errors as status messages and logs them.
+    pub fn status() -> Self {
+        Self::new().display(ErrorDisplay::Status).log(true)
+    }
+
+    /// Sets how errors are shown to the user.
+    pub fn display(mut self, display: ErrorDisplay) -> Self {
+        self.display = display;
+        self
+    }
+
+    /// Sets whether errors are logged through [`LogMessage`].
+    pub fn log(mut self, log: bool) -> Self {
+        self.log = log;
+        self
+    }
+
+    /// Sets how long error status messages stay visible. Defaults to 8 seconds.
+    pub fn duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// Sets a context prepended to error messages, e.g. `"Failed to save"` shows `"Failed to save: <error>"`.
+    pub fn context(mut self, context: impl Into<HSTRING>) -> Self {
+        self.context = Some(context.into());
+        self
+    }
+
+    /// Returns the context of the policy, or `default` if none is set.
+    pub(crate) fn context_or(&self, default: &str) -> HSTRING {
+        self.context.clone().unwrap_or_else(|| default.into())
+    }
+
+    fn message(&self, error: &Error) -> HSTRING {
+        let message = error.message();
+        let message = if message.is_empty() {
+            format!("{:?}", error.code())
+        } else {
+            message
+        };
+        match &self.context {
+            Some(context) => format!("{}: {}", context, message).into(),
+            None => message.into(),
+        }
+    }
+
+    fn report(&self, message: &HSTRING, status: bool) {
+        if self.log {
+            LogMessage::error(message.clone()).log();
+        }
+        if status {
+            let status = StatusMessageBuilder::new()
+                .state(MessageState::Error)
+                .message(message.clone())
+                .build();
+            show_status_for(status.into(), StatusContext::Extension, self.duration);
+        }
+    }
+
+    /// Logs `error` if logging is enabled, without showing it.
+    pub(crate) fn log_error(&self, error: &Error) {
+        self.report(&self.message(error), false);
+    }
+
+    /// Reports the error of a callback returning a [`CommandResult`].
+    ///
+    /// Reported errors become [`CommandResult::ShowToast`] or [`CommandResult::KeepOpen`].
+    pub(crate) fn handle_result(&self, result: Result<CommandResult>) -> Result<CommandResult> {
+        let error = match result {
+            Ok(result) => return Ok(result),
+            Err(error) => error,
+        };
+        let message = self.message(&error);
+        self.report(&message, self.display == ErrorDisplay::Status);
+        match self.display {
+            ErrorDisplay::Return => Err(error),
+            ErrorDisplay::Toast => Ok(CommandResult::ShowToast(ToastArgs::new(
+                message,
+                CommandResult::KeepOpen,
+            )?)),
+            ErrorDisplay::Status => Ok(CommandResult::KeepOpen),
+        }
+    }
+
+    /// Reports the error of a callback returning nothing.
+    ///
+    /// Reported errors are shown as status messages, even with [`ErrorDisplay::Toast`].
+    pub(crate) fn handle<T: Default>(&self, result: Result<T>) -> Result<T> {
+        match result {
+            Ok(value) => Ok(value),
+            Err(error) => {
+                let message = self.message(&error);
+                self.report(&message, self.display != ErrorDisplay::Return);
+                match self.display {
+                    ErrorDisplay::Return => Err(error),
+                    _ => Ok(T::default()),
+                }
+            }
+        }
+    }
+}
+
+impl Default for ErrorPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
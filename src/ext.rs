@@ -41,7 +41,7 @@ impl From<&CommandProvider_Impl> for Extension {
 
 impl IClosable_Impl for Extension_Impl {
     fn Close(&self) -> windows_core::Result<()> {
-        Ok(())
+        self.cmd_provider.close()
     }
 }
 
@@ -58,6 +58,6 @@ impl IExtension_Impl for Extension_Impl {
     }
 
     fn Dispose(&self) -> windows_core::Result<()> {
-        Ok(())
+        self.cmd_provider.dispose()
     }
 }
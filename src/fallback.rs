@@ -5,6 +5,7 @@ use std::ops::Deref;
 use super::cmd_item::CommandItem;
 use crate::bindings::*;
 use crate::cmd_item::CommandItem_Impl;
+use crate::error::{BoxError, ErrorPolicy, catch_panic, into_win_error};
 use crate::notify::*;
 use crate::utils::{ComBuilder, assert_send_sync};
 use windows_core::{ComObject, HSTRING, IUnknownImpl as _, Result, implement};
@@ -15,6 +16,7 @@ use windows_core::{ComObject, HSTRING, IUnknownImpl as _, Result, implement};
 #[implement(IFallbackHandler)]
 pub struct FallbackHandler {
     querier: Box<dyn Send + Sync + Fn(HSTRING) -> Result<()>>,
+    error_policy: Option<ErrorPolicy>,
 }
 
 impl FallbackHandler {
@@ -25,6 +27,7 @@ impl FallbackHandler {
     {
         Self {
             querier: Box::new(querier),
+            error_policy: None,
         }
     }
 
@@ -35,11 +38,45 @@ impl FallbackHandler {
     {
         Self::new_unmanaged(querier).into()
     }
+
+    /// Build a unmanaged fallback handler whose querier may return any error,
+    /// e.g. `anyhow::Error`, converted with [`into_win_error`].
+    pub fn new_any_unmanaged<F, E>(querier: F) -> Self
+    where
+        F: Send + Sync + Fn(HSTRING) -> std::result::Result<(), E> + 'static,
+        E: Into<BoxError>,
+    {
+        Self::new_unmanaged(move |query| querier(query).map_err(into_win_error))
+    }
+
+    /// Build a reference-counted COM object for a fallback handler whose querier may return any error,
+    /// e.g. `anyhow::Error`, converted with [`into_win_error`].
+    pub fn new_any<F, E>(querier: F) -> ComObject<Self>
+    where
+        F: Send + Sync + Fn(HSTRING) -> std::result::Result<(), E> + 'static,
+        E: Into<BoxError>,
+    {
+        Self::new_any_unmanaged(querier).into()
+    }
+
+    /// Sets how errors of the querier are reported to the user.
+    ///
+    /// Set it on an unmanaged handler, e.g. from [`FallbackHandler::new_unmanaged`], before converting it into a COM object.
+    /// Errors are shown as status messages, even with [`ErrorDisplay::Toast`][`crate::error::ErrorDisplay::Toast`].
+    /// By default, errors are returned to Command Palette without feedback to the user.
+    pub fn error_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.error_policy = Some(policy);
+        self
+    }
 }
 
 impl IFallbackHandler_Impl for FallbackHandler_Impl {
     fn UpdateQuery(&self, query: &windows_core::HSTRING) -> windows_core::Result<()> {
-        (self.querier)(query.clone())
+        let result = catch_panic(|| (self.querier)(query.clone()));
+        match &self.error_policy {
+            Some(policy) => policy.handle(result),
+            None => result,
+        }
     }
 }
 
@@ -2,15 +2,21 @@
 //!
 //! This module currently doesn't work: <https://github.com/microsoft/PowerToys/issues/38318>
 
+use crate::error::catch_panic;
+use crate::host::LogMessage;
 use crate::icon::IconInfo;
-use crate::utils::{ComBuilder, assert_send_sync, map_array};
+use crate::notify::{NotifyLock, NotifyLockReadGuard, NotifyLockWriteGuard};
+use crate::page::list::{ListItem_Impl, ListPage};
+use crate::utils::{ComBuilder, assert_send_sync, map_array, write_atomic};
 use crate::{bindings::*, utils::OkOrEmpty};
-use std::sync::RwLock;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
 use windows::{
-    Win32::Foundation::ERROR_LOCK_VIOLATION,
+    Win32::Foundation::{E_INVALIDARG, ERROR_LOCK_VIOLATION},
     core::{ComObject, HSTRING, implement},
 };
-use windows_core::{Error, Result};
+use windows_core::{Error, Interface as _, Result, Weak};
 
 /// Represents a separator in the filter list.
 /// 
@@ -21,6 +27,9 @@ pub struct FilterSeparator;
 impl ISeparatorFilterItem_Impl for FilterSeparator_Impl {}
 impl IFilterItem_Impl for FilterSeparator_Impl {}
 
+/// Predicate deciding whether a list item passes a [`Filter`].
+pub type FilterPredicate = Box<dyn Send + Sync + Fn(&ListItem_Impl) -> bool>;
+
 /// Represents a selectable filter item in the filter list.
 ///
 #[doc = include_str!("./bindings_docs/IFilter.md")]
@@ -34,6 +43,23 @@ pub struct Filter {
     pub name: HSTRING,
 }
 
+impl Filter {
+    /// Creates a filter with the given id and name.
+    pub fn new(id: impl Into<HSTRING>, name: impl Into<HSTRING>) -> Self {
+        Filter {
+            icon: None,
+            id: id.into(),
+            name: name.into(),
+        }
+    }
+
+    /// Sets the icon of the filter.
+    pub fn with_icon(mut self, icon: ComObject<IconInfo>) -> Self {
+        self.icon = Some(icon);
+        self
+    }
+}
+
 impl IFilter_Impl for Filter_Impl {
     fn Icon(&self) -> windows_core::Result<IIconInfo> {
         self.icon
@@ -69,11 +95,96 @@ impl From<&FilterItem> for IFilterItem {
     }
 }
 
+fn find_filter(items: &[FilterItem], id: &HSTRING) -> Option<ComObject<Filter>> {
+    items.iter().find_map(|item| match item {
+        FilterItem::Filter(filter) if filter.id == *id => Some(filter.clone()),
+        _ => None,
+    })
+}
+
+#[derive(Default)]
+struct SaveState {
+    /// JSON of the selection waiting to be written.
+    pending: Option<String>,
+    writing: bool,
+}
+
+/// File the selected filter is persisted to, written on a background thread.
+///
+/// Holds the id of the selected filter, or `null` if the selection was cleared.
+struct SelectionFile {
+    path: PathBuf,
+    state: Mutex<SaveState>,
+}
+
+impl SelectionFile {
+    /// Reads the saved selection, `Some(None)` if it was cleared and `None` if none was saved.
+    fn load(path: &Path) -> Option<Option<String>> {
+        let data = std::fs::read_to_string(path).ok()?;
+        serde_json::from_str(&data).ok()
+    }
+
+    /// Queues the selection of `id` to be written, starting a writer thread unless one is running.
+    ///
+    /// The writer keeps writing the latest queued selection until none is left,
+    /// so writes never overtake each other.
+    fn save(self: &Arc<Self>, id: Option<String>) {
+        let Ok(json) = serde_json::to_string(&id) else {
+            return;
+        };
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        state.pending = Some(json);
+        if state.writing {
+            return;
+        }
+        state.writing = true;
+        let file = self.clone();
+        std::thread::spawn(move || {
+            loop {
+                let json = match file.state.lock() {
+                    Ok(mut state) => match state.pending.take() {
+                        Some(json) => json,
+                        None => {
+                            state.writing = false;
+                            return;
+                        }
+                    },
+                    Err(_) => return,
+                };
+                file.write(&json);
+            }
+        });
+    }
+
+    fn write(&self, json: &str) {
+        if let Err(e) = write_atomic(&self.path, json) {
+            LogMessage::warning(
+                format!(
+                    "Failed to save selected filter to {}: {}",
+                    self.path.display(),
+                    e
+                )
+                .into(),
+            )
+            .log();
+        }
+    }
+}
+
 /// A collection of filters that can be used to filter a list.
+///
+/// See [`Filters_Impl`] for field accessors.
 #[implement(IFilters)]
 pub struct Filters {
-    items: Vec<FilterItem>,
+    items: NotifyLock<Vec<FilterItem>>,
     current: RwLock<Option<ComObject<Filter>>>,
+    persist: Option<Arc<SelectionFile>>,
+    predicates: HashMap<HSTRING, FilterPredicate>,
+    show_counts: bool,
+    counts: RwLock<HashMap<HSTRING, usize>>,
+    owner: RwLock<Option<Weak<IListPage>>>,
     on_update: Box<
         dyn Send + Sync + Fn(Option<ComObject<Filter>>, Option<ComObject<Filter>>) -> Result<()>,
     >,
@@ -82,6 +193,10 @@ pub struct Filters {
 /// Builder for [`Filters`].
 pub struct FiltersBuilder {
     items: Vec<FilterItem>,
+    default: Option<HSTRING>,
+    persist: Option<PathBuf>,
+    predicates: HashMap<HSTRING, FilterPredicate>,
+    show_counts: bool,
     on_update: Box<
         dyn Send + Sync + Fn(Option<ComObject<Filter>>, Option<ComObject<Filter>>) -> Result<()>,
     >,
@@ -92,10 +207,45 @@ impl FiltersBuilder {
     pub fn new() -> Self {
         Self {
             items: Vec::new(),
+            default: None,
+            persist: None,
+            predicates: HashMap::new(),
+            show_counts: false,
             on_update: Box::new(|_, _| Ok(())),
         }
     }
 
+    /// Sets the id of the filter which is selected initially.
+    ///
+    /// Without a default, no filter is selected until the user picks one.
+    pub fn default_filter(mut self, id: impl Into<HSTRING>) -> Self {
+        self.default = Some(id.into());
+        self
+    }
+
+    /// Persists the selected filter to a file at `path`.
+    ///
+    /// The id of the selected filter is written on a background thread after every change,
+    /// and restored when the filters are built, taking precedence over [`FiltersBuilder::default_filter`].
+    /// Clearing the selection is saved too, so no filter is selected after a restart.
+    /// A saved id which no longer matches a filter is ignored.
+    /// Parent directories will be created when writing the file, if they do not exist.
+    ///
+    /// Failing to write the file doesn't fail the selection, and is logged as a warning instead.
+    pub fn persist(mut self, path: PathBuf) -> Self {
+        self.persist = Some(path);
+        self
+    }
+
+    /// Sets whether filter names show the number of items passing each filter, like `Open (12)`.
+    ///
+    /// Counts are kept up to date by the [`ListPage`] showing the filters,
+    /// taking the search text into account if the page matches it.
+    pub fn show_counts(mut self, show_counts: bool) -> Self {
+        self.show_counts = show_counts;
+        self
+    }
+
     /// Add a [`FilterItem`].
     pub fn add(mut self, item: FilterItem) -> Self {
         self.items.push(item);
@@ -108,6 +258,19 @@ impl FiltersBuilder {
         self
     }
 
+    /// Sets the predicate which list items must pass while the filter with id `id` is selected.
+    ///
+    /// A [`ListPage`] showing the filters hides the items failing the predicate of the selected filter,
+    /// without changing its [`ListPage_Impl::items`][`crate::page::list::ListPage_Impl::items`].
+    /// Filters without a predicate let every item pass.
+    pub fn predicate<F>(mut self, id: impl Into<HSTRING>, predicate: F) -> Self
+    where
+        F: Send + Sync + Fn(&ListItem_Impl) -> bool + 'static,
+    {
+        self.predicates.insert(id.into(), Box::new(predicate));
+        self
+    }
+
     /// Add a [`FilterSeparator`].
     pub fn add_separator(mut self) -> Self {
         self.items
@@ -120,6 +283,9 @@ impl FiltersBuilder {
     /// The callback should accept old and new current filter items,
     /// Update the list of items based on the new filter,
     /// and return a `Result<()>`.
+    ///
+    /// Filters with a predicate (see [`FiltersBuilder::predicate`]) are applied by the page automatically,
+    /// before the callback is called.
     pub fn on_update<F>(mut self, func: F) -> Self
     where
         F: Send
@@ -135,14 +301,152 @@ impl FiltersBuilder {
 impl ComBuilder for FiltersBuilder {
     type Output = Filters;
     fn build_unmanaged(self) -> Self::Output {
+        let default = || {
+            self.default
+                .as_ref()
+                .and_then(|id| find_filter(&self.items, id))
+        };
+        let current = match self.persist.as_deref().and_then(SelectionFile::load) {
+            Some(Some(id)) => find_filter(&self.items, &HSTRING::from(id)).or_else(default),
+            Some(None) => None,
+            None => default(),
+        };
         Filters {
-            items: self.items,
-            current: RwLock::new(None),
+            items: NotifyLock::new(self.items),
+            current: RwLock::new(current),
+            persist: self.persist.map(|path| {
+                Arc::new(SelectionFile {
+                    path,
+                    state: Mutex::new(SaveState::default()),
+                })
+            }),
+            predicates: self.predicates,
+            show_counts: self.show_counts,
+            counts: RwLock::new(HashMap::new()),
+            owner: RwLock::new(None),
             on_update: self.on_update,
         }
     }
 }
 
+impl Filters_Impl {
+    /// Readonly access to [`IFilters::Filters`].
+    ///
+    #[doc = include_str!("./bindings_docs/IFilters/Filters.md")]
+    pub fn items(&self) -> Result<NotifyLockReadGuard<'_, Vec<FilterItem>>> {
+        self.items.read()
+    }
+
+    /// Mutable access to [`IFilters::Filters`].
+    ///
+    #[doc = include_str!("./bindings_docs/IFilters/Filters.md")]
+    ///
+    /// Notifies the host about the change through the page showing the filters when dropping the guard.
+    /// The selected filter is kept, even if it is removed from the list.
+    pub fn items_mut(&self) -> Result<NotifyLockWriteGuard<'_, Vec<FilterItem>>> {
+        self.items.write(|| self.emit_owner_changed())
+    }
+
+    /// Returns the selected filter.
+    pub fn current(&self) -> Result<Option<ComObject<Filter>>> {
+        Ok(self
+            .current
+            .read()
+            .map_err(|_| Error::from(ERROR_LOCK_VIOLATION))?
+            .clone())
+    }
+
+    /// Remembers `page` as the page showing these filters, to notify it about changes.
+    ///
+    /// Called by the page when it is built, and when the filters are assigned to it.
+    pub(crate) fn set_owner(&self, page: &IListPage) -> Result<()> {
+        let page = page.downgrade()?;
+        *self
+            .owner
+            .write()
+            .map_err(|_| Error::from(ERROR_LOCK_VIOLATION))? = Some(page);
+        Ok(())
+    }
+
+    fn owner(&self) -> Option<ComObject<ListPage>> {
+        let page = self
+            .owner
+            .read()
+            .ok()
+            .and_then(|owner| owner.as_ref().and_then(|o| o.upgrade()))?;
+        ComObject::<ListPage>::cast_from(&page).ok()
+    }
+
+    fn emit_owner_changed(&self) {
+        if let Some(page) = self.owner() {
+            page.base
+                .base
+                .emit_prop_changed(page.to_interface(), "Filters");
+        }
+    }
+
+    /// Returns whether `item` passes the filter with id `id`.
+    ///
+    /// Filters without a predicate let every item pass.
+    pub fn matches(&self, id: &HSTRING, item: &ListItem_Impl) -> bool {
+        self.predicates
+            .get(id)
+            .is_none_or(|predicate| predicate(item))
+    }
+
+    /// Updates the match counts shown in filter names, returning whether any of them changed.
+    ///
+    /// `count` receives a predicate for each filter and returns how many items pass it.
+    /// Does nothing unless [`FiltersBuilder::show_counts`] is enabled.
+    pub(crate) fn update_counts<F>(&self, count: F) -> Result<bool>
+    where
+        F: Fn(&dyn Fn(&ListItem_Impl) -> bool) -> usize,
+    {
+        if !self.show_counts {
+            return Ok(false);
+        }
+        let ids: Vec<HSTRING> = self
+            .items
+            .read()?
+            .iter()
+            .filter_map(|item| match item {
+                FilterItem::Filter(filter) => Some(filter.id.clone()),
+                FilterItem::Separator(_) => None,
+            })
+            .collect();
+        let next: HashMap<HSTRING, usize> = ids
+            .into_iter()
+            .map(|id| {
+                let n = count(&|item| self.matches(&id, item));
+                (id, n)
+            })
+            .collect();
+        let mut counts = self
+            .counts
+            .write()
+            .map_err(|_| Error::from(ERROR_LOCK_VIOLATION))?;
+        let changed = *counts != next;
+        *counts = next;
+        Ok(changed)
+    }
+
+    /// Returns `item` as shown to the host, with the match count after the name of filters.
+    fn shown_item(&self, item: &FilterItem, counts: &HashMap<HSTRING, usize>) -> IFilterItem {
+        match item {
+            FilterItem::Filter(filter) if self.show_counts => match counts.get(&filter.id) {
+                Some(count) => ComObject::new(Filter {
+                    icon: filter.icon.clone(),
+                    id: filter.id.clone(),
+                    name: format!("{} ({})", filter.name, count).into(),
+                })
+                .to_interface(),
+                None => item.into(),
+            },
+            _ => item.into(),
+        }
+    }
+}
+
 impl IFilters_Impl for Filters_Impl {
     fn CurrentFilterId(&self) -> windows_core::Result<windows_core::HSTRING> {
         self.current
@@ -154,34 +458,79 @@ impl IFilters_Impl for Filters_Impl {
     }
 
     fn Filters(&self) -> windows_core::Result<windows_core::Array<IFilterItem>> {
-        Ok(map_array(&self.items, |filter| {
-            Some(IFilterItem::from(filter))
+        let counts = self
+            .counts
+            .read()
+            .map_err(|_| Error::from(ERROR_LOCK_VIOLATION))?;
+        Ok(map_array(&self.items.read()?, |filter| {
+            Some(self.shown_item(filter, &counts))
         }))
     }
 
+    /// Selects the filter with id `value`, or no filter if `value` is empty.
+    ///
+    /// Ids not matching any filter are rejected with `E_INVALIDARG`, leaving the selection unchanged.
     fn SetCurrentFilterId(&self, value: &windows_core::HSTRING) -> windows_core::Result<()> {
-        let mut guard = self
-            .current
-            .write()
-            .map_err(|_| Error::from(ERROR_LOCK_VIOLATION))?;
-        let old = guard.clone();
-        let mut new = None;
-        for filter in self.items.iter() {
-            match filter {
-                FilterItem::Separator(_) => continue,
-                FilterItem::Filter(item) => {
-                    if item.id == *value {
-                        new = Some(item.clone());
-                        break;
-                    }
-                }
+        let new = match find_filter(&self.items.read()?, value) {
+            Some(filter) => Some(filter),
+            None if value.is_empty() => None,
+            None => {
+                return Err(Error::new(
+                    E_INVALIDARG,
+                    format!("Unknown filter id: {}", value),
+                ));
             }
+        };
+        let old = std::mem::replace(
+            &mut *self
+                .current
+                .write()
+                .map_err(|_| Error::from(ERROR_LOCK_VIOLATION))?,
+            new.clone(),
+        );
+        if let Some(file) = &self.persist {
+            file.save(new.as_ref().map(|filter| filter.id.to_string_lossy()));
         }
-        *guard = new.clone();
-        drop(guard);
-        (self.on_update)(old, new)
+        catch_panic(|| {
+            if let Some(page) = self.owner() {
+                page.refilter();
+            }
+            (self.on_update)(old, new)
+        })
     }
 }
 
 const _: () = assert_send_sync::<Filter>();
 const _: () = assert_send_sync::<ComObject<Filters>>();
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restores_saved_selection() {
+        let path = std::env::temp_dir().join(format!("cmdpal-filter-{}.json", std::process::id()));
+        let current = |saved: Option<&str>| {
+            match saved {
+                Some(saved) => std::fs::write(&path, saved).unwrap(),
+                None => {
+                    let _ = std::fs::remove_file(&path);
+                }
+            }
+            FiltersBuilder::new()
+                .add_filter(ComObject::new(Filter::new("a", "A")))
+                .add_filter(ComObject::new(Filter::new("b", "B")))
+                .default_filter("a")
+                .persist(path.clone())
+                .build()
+                .current()
+                .unwrap()
+                .map(|filter| filter.id.to_string())
+        };
+        assert_eq!(current(None).as_deref(), Some("a"));
+        assert_eq!(current(Some(r#""b""#)).as_deref(), Some("b"));
+        assert_eq!(current(Some(r#""gone""#)).as_deref(), Some("a"));
+        assert_eq!(current(Some("null")), None);
+        let _ = std::fs::remove_file(&path);
+    }
+}
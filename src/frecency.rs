@@ -0,0 +1,270 @@
+//! Usage-frequency ("frecency") ranking persisted across sessions.
+//!
+//! [`Frecency`] records invocations of commands by their [`ICommand::Id`][`crate::bindings::ICommand::Id`],
+//! and turns how often and how recently each one was used into a score boost.
+//!
+//! Share a tracker with [`Arc`], and attach it to commands with [`InvokableCommandBuilder::track_usage`][`crate::cmd::InvokableCommandBuilder::track_usage`].
+//! List pages rank their items by it with [`ListPageBuilder::rank_by_usage`][`crate::page::list::ListPageBuilder::rank_by_usage`].
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use windows::Win32::Foundation::{E_FAIL, ERROR_FILE_INVALID, ERROR_LOCK_VIOLATION};
+use windows_core::{Error, Result};
+
+use crate::host::LogMessage;
+use crate::utils::write_atomic;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct UsageEntry {
+    count: u32,
+    last_used: u64,
+    /// Sum of the weights of all uses at `last_used`, each use decaying from its own time.
+    weight: f64,
+}
+
+impl UsageEntry {
+    fn weight_at(&self, now: u64, half_life: Duration) -> f64 {
+        let age = now.saturating_sub(self.last_used) as f64;
+        let half_life = half_life.as_secs_f64().max(1.0);
+        self.weight * 0.5f64.powf(age / half_life)
+    }
+}
+
+#[derive(Default)]
+struct UsageState {
+    entries: HashMap<String, UsageEntry>,
+    write_pending: bool,
+}
+
+/// State shared with the thread writing the data file.
+struct UsageStore {
+    path: PathBuf,
+    state: Mutex<UsageState>,
+    /// Held while writing the file, so writes happen in the order of their snapshots.
+    io: Mutex<()>,
+}
+
+impl UsageStore {
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, UsageState>> {
+        self.state
+            .lock()
+            .map_err(|_| Error::from(ERROR_LOCK_VIOLATION))
+    }
+
+    /// Writes a snapshot of the entries to the data file.
+    fn write(&self) -> Result<()> {
+        let _io = self
+            .io
+            .lock()
+            .map_err(|_| Error::from(ERROR_LOCK_VIOLATION))?;
+        let json = {
+            let mut state = self.lock()?;
+            state.write_pending = false;
+            serde_json::to_string(&state.entries).map_err(|e| Error::new(E_FAIL, e.to_string()))?
+        };
+        write_atomic(&self.path, &json).map_err(|e| {
+            Error::new(
+                ERROR_FILE_INVALID.to_hresult(),
+                format!(
+                    "Failed to write usage data to {}: {}",
+                    self.path.display(),
+                    e
+                ),
+            )
+        })
+    }
+}
+
+/// Loads the entries from `path`.
+///
+/// A file which can't be parsed is logged and moved aside to `<path>.corrupt`,
+/// so it isn't overwritten by the next write.
+fn load(path: &Path) -> HashMap<String, UsageEntry> {
+    let data = match std::fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return HashMap::new(),
+        Err(e) => {
+            LogMessage::warning(
+                format!("Failed to read usage data from {}: {}", path.display(), e).into(),
+            )
+            .log();
+            return HashMap::new();
+        }
+    };
+    match serde_json::from_str::<HashMap<String, UsageEntry>>(&data) {
+        Ok(entries) => entries,
+        Err(e) => {
+            let mut corrupt = path.as_os_str().to_owned();
+            corrupt.push(".corrupt");
+            let _ = std::fs::rename(path, &corrupt);
+            LogMessage::warning(
+                format!(
+                    "Usage data in {} is corrupt and was moved to {}: {}",
+                    path.display(),
+                    Path::new(&corrupt).display(),
+                    e
+                )
+                .into(),
+            )
+            .log();
+            HashMap::new()
+        }
+    }
+}
+
+/// A usage tracker which persists invocation counts to a JSON file.
+///
+/// Recording an invocation only updates the tracker in memory.
+/// The file is written on a background thread shortly after, once for all invocations recorded meanwhile.
+pub struct Frecency {
+    store: Arc<UsageStore>,
+    half_life: Duration,
+    write_delay: Duration,
+    generation: AtomicU64,
+}
+
+impl Frecency {
+    /// Creates a tracker which stores its data at `path`.
+    ///
+    /// Previously recorded usage is loaded if the file exists.
+    /// A file which can't be parsed is logged and moved to `<path>.corrupt`.
+    /// Parent directories will be created when writing the file, if they do not exist.
+    ///
+    /// The file usually lives in the data directory of the extension,
+    /// e.g. next to the file of [`JsonCommandSettings`][`crate::settings::JsonCommandSettings`].
+    ///
+    /// Wrap the tracker in an [`Arc`] to share it between commands.
+    pub fn new(path: PathBuf) -> Self {
+        let entries = load(&path);
+        Frecency {
+            store: Arc::new(UsageStore {
+                path,
+                state: Mutex::new(UsageState {
+                    entries,
+                    write_pending: false,
+                }),
+                io: Mutex::new(()),
+            }),
+            half_life: Duration::from_secs(7 * 24 * 60 * 60),
+            write_delay: Duration::from_secs(2),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// Sets the half-life of usage.
+    ///
+    /// The weight of each usage halves every `half_life`. Defaults to 7 days.
+    pub fn half_life(mut self, half_life: Duration) -> Self {
+        self.half_life = half_life;
+        self
+    }
+
+    /// Sets how long recorded usage waits before the data file is written. Defaults to 2 seconds.
+    pub fn write_delay(mut self, write_delay: Duration) -> Self {
+        self.write_delay = write_delay;
+        self
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    }
+
+    /// Returns a number which changes whenever recorded usage changes.
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// Records an invocation of `id`.
+    ///
+    /// The data file is written on a background thread after [`Frecency::write_delay`],
+    /// failures to write it are logged.
+    pub fn record(&self, id: &str) -> Result<()> {
+        let now = Self::now();
+        let mut state = self.store.lock()?;
+        let entry = state.entries.entry(id.to_string()).or_default();
+        entry.weight = entry.weight_at(now, self.half_life) + 1.0;
+        entry.count = entry.count.saturating_add(1);
+        entry.last_used = now;
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        if !state.write_pending {
+            state.write_pending = true;
+            let store = self.store.clone();
+            let delay = self.write_delay;
+            std::thread::spawn(move || {
+                std::thread::sleep(delay);
+                if let Err(e) = store.write() {
+                    LogMessage::warning(e.message().into()).log();
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Writes the data file now, e.g. before the extension exits.
+    pub fn flush(&self) -> Result<()> {
+        self.store.write()
+    }
+
+    /// Returns the number of recorded invocations of `id`.
+    pub fn count(&self, id: &str) -> u32 {
+        self.store
+            .lock()
+            .ok()
+            .and_then(|state| state.entries.get(id).map(|e| e.count))
+            .unwrap_or(0)
+    }
+
+    /// Returns the frecency score of `id`.
+    ///
+    /// Each invocation adds `1.0` to the score, decayed by the time since that invocation.
+    /// Unknown ids score `0.0`.
+    pub fn score(&self, id: &str) -> f64 {
+        self.store
+            .lock()
+            .ok()
+            .and_then(|state| {
+                state
+                    .entries
+                    .get(id)
+                    .map(|e| e.weight_at(Self::now(), self.half_life))
+            })
+            .unwrap_or(0.0)
+    }
+
+    /// Combines a search ranking score with the frecency score of `id`.
+    ///
+    /// `weight` controls how much usage matters relative to `score`.
+    pub fn boost(&self, id: &str, score: f64, weight: f64) -> f64 {
+        score + weight * (1.0 + self.score(id)).ln()
+    }
+
+    /// Stably sorts `items` by the frecency score of their ids, most used first.
+    ///
+    /// Items without an id or without recorded usage keep their relative order after used items.
+    /// Useful for ordering top-level commands before building a
+    /// [`CommandProvider`][`crate::cmd_provider::CommandProvider`].
+    pub fn rank<T, F>(&self, items: &mut [T], id: F)
+    where
+        F: Fn(&T) -> Option<String>,
+    {
+        items.sort_by_cached_key(|item| {
+            let score = id(item).map(|id| self.score(&id)).unwrap_or(0.0);
+            // Bits of non-negative floats order the same as their values.
+            std::cmp::Reverse(score.max(0.0).to_bits())
+        });
+    }
+
+    /// Forgets all recorded usage and writes the data file.
+    pub fn clear(&self) -> Result<()> {
+        self.store.lock()?.entries.clear();
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        self.store.write()
+    }
+}
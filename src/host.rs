@@ -460,6 +460,21 @@ pub fn hide_status(message: ComObject<StatusMessage>) {
     }
 }
 
+/// Shows a status message to the user, and hides it after `duration`.
+///
+/// The message is hidden from a background thread, this function returns immediately.
+pub fn show_status_for(
+    message: ComObject<StatusMessage>,
+    context: StatusContext,
+    duration: std::time::Duration,
+) {
+    show_status(message.clone(), context);
+    std::thread::spawn(move || {
+        std::thread::sleep(duration);
+        hide_status(message);
+    });
+}
+
 /// Logs a message to the host.
 ///
 /// The message will be logged into the log file, yet not shown to the user.
@@ -2,6 +2,7 @@
 //! [Command Palette](https://learn.microsoft.com/en-us/windows/powertoys/command-palette/overview).
  
 pub mod bindings;
+pub mod cancel;
 pub mod cmd;
 pub mod cmd_item;
 pub mod cmd_provider;
@@ -9,16 +10,21 @@ pub mod cmd_result;
 pub mod content;
 pub mod ctx_item;
 pub mod details;
+pub mod error;
 pub mod ext;
 pub mod ext_factory;
 pub mod ext_registry;
 pub mod fallback;
 pub mod filter;
+pub mod frecency;
 pub mod host;
 pub mod icon;
 pub mod notify;
 pub mod page;
+pub mod platform;
 pub mod prelude;
+pub mod query;
+pub mod refresh;
 pub mod settings;
 pub mod utils;
 
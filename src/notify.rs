@@ -2,10 +2,12 @@
 use crate::bindings::*;
 use std::mem::ManuallyDrop;
 use std::ops::{Deref, DerefMut};
-use std::sync::{RwLock, RwLockWriteGuard};
+use std::sync::{PoisonError, RwLock, RwLockWriteGuard};
 use windows::Foundation::TypedEventHandler;
-use windows::Win32::Foundation::ERROR_LOCK_VIOLATION;
-use windows_core::{Event, IInspectable, Result, implement};
+use windows::Win32::Foundation::E_NOINTERFACE;
+use windows_core::{
+    ComObjectInner, Event, GUID, IInspectable, IUnknown, Interface, Result, implement,
+};
 
 /// `NotifyLock` struct is a wrapper around [`RwLock`] that allows for notification callbacks.
 /// When exposing the interface, `NotifyLock` references shouldn't be returned directly.
@@ -13,6 +15,10 @@ use windows_core::{Event, IInspectable, Result, implement};
 /// to ensure that intended notification function is called.
 ///
 /// Useful for implementing COM interfaces like `INotifyPropChanged`.
+///
+/// A lock poisoned by a panic while a guard was held, e.g. one caught by
+/// [`catch_panic`][`crate::error::catch_panic`], is recovered:
+/// later guards see the value as the panicking code left it.
 pub struct NotifyLock<T> {
     lock: RwLock<T>,
 }
@@ -62,7 +68,7 @@ impl<T> NotifyLock<T> {
 
     /// Get a read guard for the lock.
     pub fn read(&self) -> Result<NotifyLockReadGuard<'_, T>> {
-        self.lock.read().map_err(|_| ERROR_LOCK_VIOLATION.into())
+        Ok(self.lock.read().unwrap_or_else(PoisonError::into_inner))
     }
 
     /// Get a mutable write guard for the lock, with a notification callback.
@@ -71,14 +77,11 @@ impl<T> NotifyLock<T> {
     where
         F: Fn() + 'a,
     {
-        self.lock
-            .write()
-            .map(|guard| NotifyLockWriteGuard {
-                guard: ManuallyDrop::new(guard),
-                peeker: Box::new(|_| ()),
-                notifier: Box::new(move |_| notifier()),
-            })
-            .map_err(|_| ERROR_LOCK_VIOLATION.into())
+        Ok(NotifyLockWriteGuard {
+            guard: ManuallyDrop::new(self.lock.write().unwrap_or_else(PoisonError::into_inner)),
+            peeker: Box::new(|_| ()),
+            notifier: Box::new(move |_| notifier()),
+        })
     }
 
     pub fn write_with_peek<'a, PF, NF, P>(
@@ -90,17 +93,87 @@ impl<T> NotifyLock<T> {
         PF: Fn(&T) -> P + 'a,
         NF: Fn(P) + 'a,
     {
-        self.lock
-            .write()
-            .map(|guard| NotifyLockWriteGuard {
-                guard: ManuallyDrop::new(guard),
-                peeker: Box::new(peeker),
-                notifier: Box::new(notifier),
-            })
-            .map_err(|_| ERROR_LOCK_VIOLATION.into())
+        Ok(NotifyLockWriteGuard {
+            guard: ManuallyDrop::new(self.lock.write().unwrap_or_else(PoisonError::into_inner)),
+            peeker: Box::new(peeker),
+            notifier: Box::new(notifier),
+        })
+    }
+}
+
+/// A wrapper around an [`Event`] which can tell whether any handler is subscribed.
+///
+/// Opt in by declaring an event as e.g. `TrackedEvent<PropChangedEventHandler>` instead of [`PropChangedEventHandler`].
+/// Lets the extension know whether the host is listening, e.g. to pause refreshing unobserved content.
+/// Dereferences to the wrapped [`Event`].
+pub struct TrackedEvent<E>(E);
+
+impl<T: Interface> TrackedEvent<Event<T>> {
+    /// Creates an event with no handlers.
+    pub fn new() -> Self {
+        TrackedEvent(Event::new())
+    }
+
+    /// Returns the number of handlers subscribed to the event.
+    ///
+    /// Every handler is probed without invoking it, and only the ones responding are counted.
+    /// Handlers whose process is gone are removed, like when the event is called.
+    pub fn handler_count(&self) -> usize {
+        let mut count = 0;
+        self.0.call(|handler| {
+            probe(handler)?;
+            count += 1;
+            Ok(())
+        });
+        count
+    }
+
+    /// Returns whether any handler is subscribed.
+    pub fn has_handlers(&self) -> bool {
+        self.handler_count() > 0
+    }
+}
+
+/// Asks `handler` for an interface no handler implements.
+///
+/// The proxy of a handler in another process forwards the query, so a handler whose process is gone
+/// fails with the same RPC errors as when it is invoked, instead of `E_NOINTERFACE`.
+fn probe(handler: &impl Interface) -> Result<()> {
+    const PROBE: GUID = GUID::from_u128(0x6c0f_58a2_93d4_4b7e_a1f5_2e8c_47b9_d031);
+    let mut interface = std::ptr::null_mut();
+    let code = unsafe { handler.query(&PROBE, &mut interface) };
+    if code.is_ok() {
+        drop(unsafe { IUnknown::from_raw(interface) });
+        return Ok(());
+    }
+    if code == E_NOINTERFACE {
+        return Ok(());
+    }
+    Err(code.into())
+}
+
+impl<T: Interface> Default for TrackedEvent<Event<T>> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E> Deref for TrackedEvent<E> {
+    type Target = E;
+
+    fn deref(&self) -> &E {
+        &self.0
     }
 }
 
+/// COM objects whose change events can be observed by the host.
+///
+/// Used by [`RefreshScheduler::pause_when_unobserved`][`crate::refresh::RefreshScheduler::pause_when_unobserved`].
+pub trait Observable: ComObjectInner {
+    /// Returns whether any handler is subscribed to `PropChanged` or `ItemsChanged` events of the object.
+    fn is_observed(this: &Self::Outer) -> bool;
+}
+
 pub type PropChangedEventHandler = Event<TypedEventHandler<IInspectable, IPropChangedEventArgs>>;
 
 /// `PropChangedEventArgs` is used to notify about property changes in COM interfaces.
@@ -138,3 +211,53 @@ impl From<i32> for ItemsChangedEventArgs {
         ItemsChangedEventArgs(value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::panic::{AssertUnwindSafe, catch_unwind};
+
+    #[test]
+    fn recovers_from_panic_while_writing() {
+        let lock = NotifyLock::new(1);
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            let mut guard = lock.write(|| {}).unwrap();
+            *guard = 2;
+            panic!("while holding the guard");
+        }));
+        assert!(result.is_err());
+        assert_eq!(*lock.read().unwrap(), 2);
+
+        let notified = Cell::new(false);
+        *lock.write(|| notified.set(true)).unwrap() = 3;
+        assert!(notified.get());
+        assert_eq!(*lock.read().unwrap(), 3);
+    }
+
+    #[test]
+    fn peeks_after_recovering() {
+        let lock = NotifyLock::new(1);
+        let _ = catch_unwind(AssertUnwindSafe(|| {
+            let _guard = lock.write_with_peek(|v| *v, |_| {}).unwrap();
+            panic!("while holding the guard");
+        }));
+        let peeked = Cell::new(0);
+        *lock.write_with_peek(|v| *v, |v| peeked.set(v)).unwrap() = 4;
+        assert_eq!(peeked.get(), 4);
+    }
+
+    #[test]
+    fn counts_subscribed_handlers() {
+        let event = TrackedEvent::<PropChangedEventHandler>::new();
+        assert!(!event.has_handlers());
+        let handler = || TypedEventHandler::new(|_, _| Ok(()));
+        let token = event.add(&handler()).unwrap();
+        event.add(&handler()).unwrap();
+        assert_eq!(event.handler_count(), 2);
+        event.remove(token);
+        assert_eq!(event.handler_count(), 1);
+        event.clear();
+        assert!(!event.has_handlers());
+    }
+}
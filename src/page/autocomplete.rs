@@ -0,0 +1,327 @@
+//! Autocompletion of the search text through [`IListItem::TextToSuggest`][`crate::bindings::IListItem::TextToSuggest`].
+//!
+//! [`Autocomplete`] computes a suggestion for every item of a page from the current search text,
+//! so the user can accept a completion of what they typed from the selected item.
+
+use std::sync::Mutex;
+
+use crate::query::{QuerySyntax, Term};
+use windows::Win32::Foundation::ERROR_LOCK_VIOLATION;
+use windows_core::{ComObject, Error, HSTRING, Result};
+
+use super::list::{ListItem, ListItem_Impl, ListPage_Impl};
+
+type TextBox = Box<dyn Send + Sync + Fn(&ListItem_Impl) -> Result<HSTRING>>;
+
+/// Sorted index from lowercase words to the items containing them.
+struct PrefixIndex {
+    items: Vec<(ComObject<ListItem>, u64)>,
+    texts: Vec<String>,
+    words: Vec<(String, usize, String)>,
+}
+
+/// Splits `texts` into words, returning them lowercase with the index of their text and their original case,
+/// sorted by the lowercase word.
+fn split_words(texts: &[&str]) -> Vec<(String, usize, String)> {
+    let mut words = Vec::new();
+    for (index, text) in texts.iter().enumerate() {
+        for word in text.split(|c: char| !c.is_alphanumeric()) {
+            if !word.is_empty() {
+                words.push((word.to_lowercase(), index, word.to_string()));
+            }
+        }
+    }
+    words.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    words
+}
+
+impl PrefixIndex {
+    fn build(items: &[ComObject<ListItem>], text_fn: &TextBox) -> Result<Self> {
+        let mut texts = Vec::with_capacity(items.len());
+        let mut indexed = Vec::with_capacity(items.len());
+        for item in items {
+            indexed.push((item.clone(), item.base.text_generation()));
+            texts.push(text_fn(item)?.to_string_lossy());
+        }
+        let words = split_words(&texts.iter().map(String::as_str).collect::<Vec<_>>());
+        Ok(PrefixIndex {
+            items: indexed,
+            texts,
+            words,
+        })
+    }
+
+    /// Returns whether the index was built from `items`, and none of their texts changed since.
+    fn is_for(&self, items: &[ComObject<ListItem>]) -> bool {
+        self.items.len() == items.len()
+            && self.items.iter().zip(items).all(|((a, generation), b)| {
+                std::ptr::eq(a.get(), b.get()) && *generation == b.base.text_generation()
+            })
+    }
+
+    /// Returns for every item the completion of `partial` by its words, if any.
+    ///
+    /// The completion is the longest common prefix of all words of the item starting with `partial`,
+    /// in the case of the first of them, and must be longer than `partial`.
+    fn complete_words(&self, partial: &str) -> Vec<Option<String>> {
+        let partial = partial.to_lowercase();
+        let mut matches: Vec<Option<(&str, &str)>> = vec![None; self.texts.len()];
+        let start = self
+            .words
+            .partition_point(|(w, _, _)| w.as_str() < partial.as_str());
+        for (lower, index, word) in self.words[start..].iter() {
+            if !lower.starts_with(&partial) {
+                break;
+            }
+            matches[*index] = match matches[*index] {
+                None => Some((lower, word)),
+                Some((common, word)) => Some((&common[..common_prefix_len(common, lower)], word)),
+            };
+        }
+        matches
+            .into_iter()
+            .map(|m| {
+                let (common, word) = m?;
+                (common.len() > partial.len()).then(|| restore_case(common, word))
+            })
+            .collect()
+    }
+}
+
+/// Returns the byte length of the longest common prefix of `a` and `b`.
+fn common_prefix_len(a: &str, b: &str) -> usize {
+    a.char_indices()
+        .zip(b.chars())
+        .find(|((_, x), y)| x != y)
+        .map_or(a.len().min(b.len()), |((i, _), _)| i)
+}
+
+/// Returns the prefix of `word` matching its lowercase prefix `lower`,
+/// or `lower` itself if lowercasing changed the length of `word`.
+fn restore_case(lower: &str, word: &str) -> String {
+    let chars = lower.chars().count();
+    if word.to_lowercase().chars().count() == word.chars().count() {
+        word.chars().take(chars).collect()
+    } else {
+        lower.to_string()
+    }
+}
+
+fn is_separator(c: char) -> bool {
+    c == '/' || c == '\\'
+}
+
+/// Completes `search` to the next path segment of `text`, if `text` is path-like and starts with `search`.
+fn complete_path(text: &str, search: &str) -> Option<String> {
+    if !text.contains(is_separator)
+        || text.len() <= search.len()
+        || !text.is_char_boundary(search.len())
+        || !text[..search.len()].eq_ignore_ascii_case(search)
+    {
+        return None;
+    }
+    let rest = &text[search.len()..];
+    // Skip a separator right at the cursor, so the suggestion always adds a segment.
+    let skip = rest.chars().take_while(|c| is_separator(*c)).count();
+    let end = rest[skip..]
+        .find(is_separator)
+        .map(|i| skip + i + 1)
+        .unwrap_or(rest.len());
+    Some(format!("{}{}", search, &rest[..end]))
+}
+
+/// Computes [`IListItem::TextToSuggest`][`crate::bindings::IListItem::TextToSuggest`] of list items from the search text.
+///
+/// For each item, the first applicable completion of the search text is suggested:
+///
+/// 1. the value of a `key:` token being typed, if a [`QuerySyntax`] is set,
+///    see [`QuerySyntax::suggest`],
+/// 2. the next path segment, for items whose text is a path starting with the search text,
+/// 3. the longest common prefix of the words of the item which start with the word being typed.
+///
+/// Items without a completion suggest nothing.
+/// Words are looked up in a sorted prefix index, which is only rebuilt when the items,
+/// or their titles or subtitles, change.
+/// A custom [`Autocomplete::text_fn`] reading other data should call [`Autocomplete::invalidate`] when it changes.
+///
+/// Attach it to a page with [`DynamicListPageBuilder::autocomplete`][`super::dyn_list::DynamicListPageBuilder::autocomplete`],
+/// or call [`Autocomplete::apply`] after updating the items.
+pub struct Autocomplete {
+    syntax: Option<QuerySyntax>,
+    text_fn: TextBox,
+    index: Mutex<Option<PrefixIndex>>,
+}
+
+impl Autocomplete {
+    /// Creates an autocompletion which completes against the titles of items.
+    pub fn new() -> Self {
+        Autocomplete {
+            syntax: None,
+            text_fn: Box::new(|item| Ok(item.title()?.clone())),
+            index: Mutex::new(None),
+        }
+    }
+
+    /// Sets the query syntax used to complete `key:` values.
+    pub fn syntax(mut self, syntax: QuerySyntax) -> Self {
+        self.syntax = Some(syntax);
+        self
+    }
+
+    /// Sets the function returning the text of an item to complete against.
+    ///
+    /// Defaults to the title of the item. Use e.g. the full path for file items.
+    pub fn text_fn<F>(mut self, text_fn: F) -> Self
+    where
+        F: Send + Sync + Fn(&ListItem_Impl) -> Result<HSTRING> + 'static,
+    {
+        self.text_fn = Box::new(text_fn);
+        *self.index.get_mut().unwrap_or_else(|e| e.into_inner()) = None;
+        self
+    }
+
+    /// Drops the prefix index, so it is rebuilt by the next suggestion.
+    pub fn invalidate(&self) {
+        *self.index.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    /// Computes the suggestion of every item for `search`, in order of `items`.
+    pub fn suggest(&self, search: &str, items: &[ComObject<ListItem>]) -> Result<Vec<HSTRING>> {
+        if let Some(syntax) = &self.syntax
+            && let Some(token) = syntax.parse(search).partial()
+            && matches!(token.term, Term::Field { .. })
+        {
+            let suggestion = syntax
+                .suggest(search)
+                .map(HSTRING::from)
+                .unwrap_or_default();
+            return Ok(vec![suggestion; items.len()]);
+        }
+
+        let mut index = self
+            .index
+            .lock()
+            .map_err(|_| Error::from(ERROR_LOCK_VIOLATION))?;
+        if !index.as_ref().is_some_and(|i| i.is_for(items)) {
+            *index = Some(PrefixIndex::build(items, &self.text_fn)?);
+        }
+        let Some(index) = index.as_ref() else {
+            return Ok(Vec::new());
+        };
+
+        let partial_start = search
+            .rfind(char::is_whitespace)
+            .map(|i| i + search[i..].chars().next().map_or(1, char::len_utf8))
+            .unwrap_or(0);
+        let partial = &search[partial_start..];
+        let words = if partial.is_empty() {
+            vec![None; items.len()]
+        } else {
+            index.complete_words(partial)
+        };
+
+        Ok(index
+            .texts
+            .iter()
+            .zip(words)
+            .map(|(text, word)| {
+                if !search.is_empty()
+                    && let Some(path) = complete_path(text, search)
+                {
+                    return HSTRING::from(path);
+                }
+                match word {
+                    Some(word) => format!("{}{}", &search[..partial_start], word).into(),
+                    None => HSTRING::new(),
+                }
+            })
+            .collect())
+    }
+
+    /// Updates the suggestion of every item of `page` for its current search text.
+    ///
+    /// Only suggestions which differ are written, so the host is notified for actual changes only.
+    pub fn apply(&self, page: &ListPage_Impl) -> Result<()> {
+        let search = page.search_text()?.to_string_lossy();
+        let items = page.items()?.clone();
+        let suggestions = self.suggest(&search, &items)?;
+        for (item, suggestion) in items.iter().zip(suggestions) {
+            if *item.suggestion()? != suggestion {
+                *item.suggestion_mut()? = suggestion;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for Autocomplete {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(texts: &[&str]) -> PrefixIndex {
+        PrefixIndex {
+            items: Vec::new(),
+            texts: texts.iter().map(|t| t.to_string()).collect(),
+            words: split_words(texts),
+        }
+    }
+
+    #[test]
+    fn completes_common_prefix_of_words() {
+        let index = index(&["Configure Config", "cat car", "Command", "dog"]);
+        assert_eq!(
+            index.complete_words("co"),
+            [
+                Some("Config".to_string()),
+                None,
+                Some("Command".to_string()),
+                None
+            ]
+        );
+        assert_eq!(
+            index.complete_words("c"),
+            [
+                Some("Config".to_string()),
+                Some("ca".to_string()),
+                Some("Command".to_string()),
+                None
+            ]
+        );
+        assert_eq!(index.complete_words("ca"), [None, None, None, None]);
+    }
+
+    #[test]
+    fn skips_complete_words() {
+        let index = index(&["dog"]);
+        assert_eq!(index.complete_words("dog"), [None]);
+        assert_eq!(index.complete_words("DO"), [Some("dog".to_string())]);
+    }
+
+    #[test]
+    fn common_prefix() {
+        assert_eq!(common_prefix_len("config", "configure"), 6);
+        assert_eq!(common_prefix_len("cat", "car"), 2);
+        assert_eq!(common_prefix_len("", "car"), 0);
+        assert_eq!(common_prefix_len("über", "übel"), "übe".len());
+    }
+
+    #[test]
+    fn completes_paths() {
+        assert_eq!(
+            complete_path(r"C:\Users\me\file.txt", r"c:\us").as_deref(),
+            Some(r"c:\users\")
+        );
+        assert_eq!(
+            complete_path(r"C:\Users\me\file.txt", r"C:\Users").as_deref(),
+            Some(r"C:\Users\me\")
+        );
+        assert_eq!(complete_path("plain text", "pla"), None);
+        assert_eq!(complete_path("a/b", "a/b"), None);
+    }
+}
@@ -8,9 +8,9 @@ use crate::content::Content;
 use crate::ctx_item::ContextItem;
 use crate::details::Details;
 use crate::notify::*;
-use crate::utils::{ComBuilder, OkOrEmpty, assert_send_sync, map_array};
+use crate::utils::{ArrayCache, ComBuilder, OkOrEmpty, assert_send_sync, map_array};
 use std::ops::Deref;
-use windows_core::{ComObject, Event, IInspectable, IUnknownImpl as _, Result, implement};
+use windows_core::{ComObject, IInspectable, IUnknownImpl as _, Result, implement};
 
 /// Represents a content page that can display various types of content.
 ///
@@ -22,8 +22,9 @@ pub struct ContentPage {
     pub base: ComObject<BasePage>,
     context_menu: NotifyLock<Vec<ContextItem>>,
     contents: NotifyLock<Vec<Content>>,
+    contents_cache: ArrayCache<IContent, Content>,
     details: NotifyLock<Option<ComObject<Details>>>,
-    item_event: ItemsChangedEventHandler,
+    item_event: TrackedEvent<ItemsChangedEventHandler>,
 }
 
 /// Builder for [`ContentPage`].
@@ -84,13 +85,17 @@ impl ContentPageBuilder {
 
 impl ComBuilder for ContentPageBuilder {
     type Output = ContentPage;
+    fn build(self) -> ComObject<ContentPage> {
+        crate::cmd::registered(self.build_unmanaged().into())
+    }
     fn build_unmanaged(self) -> ContentPage {
         ContentPage {
             base: self.base,
             context_menu: NotifyLock::new(self.context_menu),
             contents: NotifyLock::new(self.contents),
+            contents_cache: ArrayCache::new(),
             details: NotifyLock::new(self.details),
-            item_event: Event::new(),
+            item_event: TrackedEvent::new(),
         }
     }
 }
@@ -143,8 +148,13 @@ impl ContentPage_Impl {
     ///
     /// Notifies the host about the change when dropping the guard.
     pub fn contents_mut(&self) -> Result<NotifyLockWriteGuard<'_, Vec<Content>, usize>> {
-        self.contents
-            .write_with_peek(|v| v.len(), |len| self.emit_self_items_changed(len as i32))
+        self.contents.write_with_peek(
+            |v| {
+                self.contents_cache.invalidate();
+                v.len()
+            },
+            |len| self.emit_self_items_changed(len as i32),
+        )
     }
 
     /// Readonly access to [`IContentPage::Details`].
@@ -174,7 +184,10 @@ impl IContentPage_Impl for ContentPage_Impl {
     }
 
     fn GetContent(&self) -> windows_core::Result<windows_core::Array<IContent>> {
-        Ok(map_array(&self.contents.read()?, |x| Some(x.into())))
+        let contents = self.contents.read()?;
+        Ok(self
+            .contents_cache
+            .get_or_init(|| contents.iter(), |x| Some(x.into())))
     }
 
     fn Details(&self) -> windows_core::Result<IDetails> {
@@ -186,6 +199,12 @@ impl IContentPage_Impl for ContentPage_Impl {
     }
 }
 
+impl Observable for ContentPage {
+    fn is_observed(this: &ContentPage_Impl) -> bool {
+        this.item_event.has_handlers() || BasePage::is_observed(&this.base)
+    }
+}
+
 impl INotifyItemsChanged_Impl for ContentPage_Impl {
     fn ItemsChanged(
         &self,
@@ -1,14 +1,23 @@
 //! Dynamic list page that can customize items based on search text.
 use std::ops::Deref;
+use std::sync::Mutex;
 
 use crate::{
     bindings::*,
+    cmd::InvokableCommand,
+    error::{BoxError, ErrorPolicy, catch_panic, into_win_error},
+    notify::Observable,
     page::list::ListPage_Impl,
+    query::{Query, QuerySyntax},
     utils::{ComBuilder, assert_send_sync},
 };
-use windows_core::{ComObject, HSTRING, Result, implement};
+use windows::Win32::Foundation::ERROR_LOCK_VIOLATION;
+use windows_core::{
+    ComObject, Error, HSTRING, IUnknownImpl as _, Interface as _, Result, implement,
+};
 
-use super::list::ListPage;
+use super::autocomplete::Autocomplete;
+use super::list::{ListPage, retry_command};
 
 pub type SearchTextUpdateBox =
     Box<dyn Send + Sync + Fn(&DynamicListPage_Impl, HSTRING, HSTRING) -> Result<()>>;
@@ -27,12 +36,18 @@ pub type SearchTextUpdateBox =
 pub struct DynamicListPage {
     pub base: ComObject<ListPage>,
     update_fn: SearchTextUpdateBox,
+    show_errors: bool,
+    autocomplete: Option<Autocomplete>,
+    error_policy: Option<ErrorPolicy>,
 }
 
 /// Builder for [`DynamicListPage`].
 pub struct DynamicListPageBuilder {
     base: ComObject<ListPage>,
     update_fn: SearchTextUpdateBox,
+    show_errors: bool,
+    autocomplete: Option<Autocomplete>,
+    error_policy: Option<ErrorPolicy>,
 }
 
 impl DynamicListPageBuilder {
@@ -41,6 +56,9 @@ impl DynamicListPageBuilder {
         DynamicListPageBuilder {
             base,
             update_fn: Box::new(|_, _, _| Ok(())),
+            show_errors: false,
+            autocomplete: None,
+            error_policy: None,
         }
     }
 
@@ -63,14 +81,99 @@ impl DynamicListPageBuilder {
         self.update_fn = Box::new(update_fn);
         self
     }
+
+    /// Like [`DynamicListPageBuilder::update_fn`], but the update function may return any error,
+    /// e.g. `anyhow::Error`, converted with [`into_win_error`].
+    pub fn update_fn_any<F, E>(self, update_fn: F) -> Self
+    where
+        F: Send
+            + Sync
+            + Fn(&DynamicListPage_Impl, HSTRING, HSTRING) -> std::result::Result<(), E>
+            + 'static,
+        E: Into<BoxError>,
+    {
+        self.update_fn(move |page, old, new| update_fn(page, old, new).map_err(into_win_error))
+    }
+
+    /// Sets a function to handle the search text parsed as a [`Query`].
+    ///
+    /// This replaces the update function set by [`DynamicListPageBuilder::update_fn`],
+    /// calling `query_fn` with the new search text parsed by `syntax`.
+    ///
+    /// After `query_fn` returns, the [`IListItem::TextToSuggest`] of the items of the page
+    /// is set to the completion of the search text by [`QuerySyntax::suggest`],
+    /// or cleared if there is nothing to complete.
+    /// Items with a suggestion of their own, i.e. one not set by a previous update, are left untouched.
+    pub fn query_fn<F>(self, syntax: QuerySyntax, query_fn: F) -> Self
+    where
+        F: Send + Sync + Fn(&DynamicListPage_Impl, Query) -> Result<()> + 'static,
+    {
+        let applied = Mutex::new(HSTRING::new());
+        self.update_fn(move |page, _, new| {
+            let text = new.to_string_lossy();
+            query_fn(page, syntax.parse(&text))?;
+            let suggestion = syntax.suggest(&text).map(HSTRING::from).unwrap_or_default();
+            let mut applied = applied
+                .lock()
+                .map_err(|_| Error::from(ERROR_LOCK_VIOLATION))?;
+            for item in page.items()?.iter() {
+                let current = item.suggestion()?.clone();
+                if (current.is_empty() || current == *applied) && current != suggestion {
+                    *item.suggestion_mut()? = suggestion.clone();
+                }
+            }
+            *applied = suggestion;
+            Ok(())
+        })
+    }
+
+    /// Sets the autocompletion of the search text.
+    ///
+    /// After each successful update, the [`IListItem::TextToSuggest`] of every item of the page
+    /// is computed by [`Autocomplete::apply`], replacing suggestions set by the update function.
+    pub fn autocomplete(mut self, autocomplete: Autocomplete) -> Self {
+        self.autocomplete = Some(autocomplete);
+        self
+    }
+
+    /// Sets whether errors of the update function are shown to the user.
+    ///
+    /// If enabled, a failed update clears the items and shows the error as the empty content of the page,
+    /// with a command to retry the update with the current search text,
+    /// instead of returning the error to the host.
+    /// Its title is the context of the [`DynamicListPageBuilder::error_policy`] if set,
+    /// or `"Search failed"`.
+    /// The error is removed by the next successful update.
+    ///
+    /// See [`ListPage_Impl::show_error`] for details.
+    pub fn show_errors(mut self, show_errors: bool) -> Self {
+        self.show_errors = show_errors;
+        self
+    }
+
+    /// Sets how errors of the update function are reported to the user.
+    ///
+    /// Errors are shown as status messages, even with [`ErrorDisplay::Toast`][`crate::error::ErrorDisplay::Toast`].
+    /// If [`DynamicListPageBuilder::show_errors`] is enabled, errors are only logged,
+    /// as they are already shown as the empty content of the page.
+    pub fn error_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.error_policy = Some(policy);
+        self
+    }
 }
 
 impl ComBuilder for DynamicListPageBuilder {
     type Output = DynamicListPage;
+    fn build(self) -> ComObject<DynamicListPage> {
+        crate::cmd::registered(self.build_unmanaged().into())
+    }
     fn build_unmanaged(self) -> DynamicListPage {
         DynamicListPage {
             base: self.base,
             update_fn: self.update_fn,
+            show_errors: self.show_errors,
+            autocomplete: self.autocomplete,
+            error_policy: self.error_policy,
         }
     }
 }
@@ -83,14 +186,47 @@ impl Deref for DynamicListPage {
     }
 }
 
+impl DynamicListPage_Impl {
+    fn search_retry(&self) -> Result<ComObject<InvokableCommand>> {
+        let weak = self.to_interface::<IDynamicListPage>().downgrade()?;
+        Ok(retry_command(move || {
+            if let Some(page) = weak.upgrade() {
+                page.SetSearchText(&page.SearchText()?)?;
+            }
+            Ok(())
+        }))
+    }
+}
+
 impl IDynamicListPage_Impl for DynamicListPage_Impl {
     fn SetSearchText(&self, value: &windows_core::HSTRING) -> windows_core::Result<()> {
         let old = self.base.search_text()?.clone();
-        let mut guard = self.base.search_text_mut_no_notify()?;
-        *guard = value.clone();
-        let new = value.clone();
-        (self.update_fn)(self, old, new)?;
-        Ok(())
+        *self.base.search_text_mut_no_notify()? = value.clone();
+        self.base.search_text_changed(&old, value);
+        match catch_panic(|| (self.update_fn)(self, old, value.clone())) {
+            Ok(()) => {
+                self.base.clear_error()?;
+                match &self.autocomplete {
+                    Some(autocomplete) => catch_panic(|| autocomplete.apply(&self.base)),
+                    None => Ok(()),
+                }
+            }
+            Err(e) if self.show_errors => {
+                let title = match &self.error_policy {
+                    Some(policy) => {
+                        policy.log_error(&e);
+                        policy.context_or("Search failed")
+                    }
+                    None => "Search failed".into(),
+                };
+                self.base.items_mut()?.clear();
+                self.base.show_error(title, &e, Some(self.search_retry()?))
+            }
+            Err(e) => match &self.error_policy {
+                Some(policy) => policy.handle(Err(e)),
+                None => Err(e),
+            },
+        }
     }
 }
 
@@ -146,6 +282,12 @@ impl IPage_Impl for DynamicListPage_Impl {
     }
 }
 
+impl Observable for DynamicListPage {
+    fn is_observed(this: &DynamicListPage_Impl) -> bool {
+        ListPage::is_observed(&this.base)
+    }
+}
+
 impl INotifyItemsChanged_Impl for DynamicListPage_Impl {
     fn ItemsChanged(
         &self,
@@ -0,0 +1,228 @@
+//! Gallery pages which display image tiles in a grid.
+//!
+//! A gallery is a [`ListPage`] with [`GridProperties`],
+//! whose items show thumbnails as their icons.
+//! Thumbnails are loaded on a background thread once the page is first shown,
+//! and each item is updated through [`CommandItem_Impl::icon_mut`][`crate::cmd_item::CommandItem_Impl::icon_mut`]
+//! as its image arrives, so large galleries show up immediately.
+//! Loading stops when the page is dropped, and skips items which were dropped meanwhile.
+
+use std::path::PathBuf;
+
+use crate::bindings::IListItem;
+use crate::cancel::CancellationToken;
+use crate::host::LogMessage;
+use crate::icon::{IconData, IconInfo};
+use crate::utils::{ComBuilder, GridProperties};
+use windows_core::{ComObject, Interface as _, Result, Weak};
+
+use super::list::{ListItem, ListPage, ListPageBuilder};
+
+/// Named tile size presets for galleries.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TileSize {
+    /// 64 × 64 tiles, e.g. for emoji or icons.
+    Small,
+    /// 128 × 128 tiles.
+    #[default]
+    Medium,
+    /// 256 × 256 tiles, e.g. for screenshots.
+    Large,
+    /// Tiles of custom width and height.
+    Custom(f32, f32),
+}
+
+impl TileSize {
+    /// Returns the width and height of the tiles.
+    pub fn size(self) -> (f32, f32) {
+        match self {
+            TileSize::Small => (64.0, 64.0),
+            TileSize::Medium => (128.0, 128.0),
+            TileSize::Large => (256.0, 256.0),
+            TileSize::Custom(width, height) => (width, height),
+        }
+    }
+}
+
+impl From<TileSize> for GridProperties {
+    fn from(value: TileSize) -> Self {
+        GridProperties::from(value.size())
+    }
+}
+
+/// Source of a thumbnail image.
+pub enum Thumbnail {
+    /// Path to an image file.
+    Path(PathBuf),
+    /// Raw image data.
+    Bytes(Vec<u8>),
+    /// An already loaded icon.
+    Icon(ComObject<IconInfo>),
+}
+
+impl Thumbnail {
+    /// Loads the thumbnail into an [`IconInfo`].
+    pub fn load(self) -> Result<ComObject<IconInfo>> {
+        match self {
+            Thumbnail::Path(path) => Ok(IconInfo::new(IconData::try_from(path)?)),
+            Thumbnail::Bytes(bytes) => Ok(IconInfo::new(IconData::try_from(bytes)?)),
+            Thumbnail::Icon(icon) => Ok(icon),
+        }
+    }
+}
+
+impl From<PathBuf> for Thumbnail {
+    fn from(value: PathBuf) -> Self {
+        Thumbnail::Path(value)
+    }
+}
+
+impl From<Vec<u8>> for Thumbnail {
+    fn from(value: Vec<u8>) -> Self {
+        Thumbnail::Bytes(value)
+    }
+}
+
+impl From<ComObject<IconInfo>> for Thumbnail {
+    fn from(value: ComObject<IconInfo>) -> Self {
+        Thumbnail::Icon(value)
+    }
+}
+
+type ThumbnailLoader = Box<dyn Send + FnOnce() -> Result<Thumbnail>>;
+
+/// Builder for a gallery [`ListPage`].
+pub struct GalleryPageBuilder {
+    list: ListPageBuilder,
+    tile_size: TileSize,
+    placeholder_icon: Option<ComObject<IconInfo>>,
+    tiles: Vec<(ComObject<ListItem>, ThumbnailLoader)>,
+}
+
+impl GalleryPageBuilder {
+    /// Creates a new builder on top of a list page builder.
+    ///
+    /// Grid properties of `list` are overridden by [`GalleryPageBuilder::tile_size`],
+    /// and tiles are appended after the items already in `list`.
+    pub fn new(list: ListPageBuilder) -> Self {
+        GalleryPageBuilder {
+            list,
+            tile_size: TileSize::default(),
+            placeholder_icon: None,
+            tiles: Vec::new(),
+        }
+    }
+
+    /// Sets the size of the tiles.
+    ///
+    /// Defaults to [`TileSize::Medium`].
+    pub fn tile_size(mut self, tile_size: TileSize) -> Self {
+        self.tile_size = tile_size;
+        self
+    }
+
+    /// Sets the icon shown on tiles until their thumbnail is loaded.
+    ///
+    /// Tiles whose item already has an icon keep it until then.
+    pub fn placeholder_icon(mut self, icon: ComObject<IconInfo>) -> Self {
+        self.placeholder_icon = Some(icon);
+        self
+    }
+
+    /// Adds a tile showing `thumbnail`.
+    pub fn add_tile(self, item: ComObject<ListItem>, thumbnail: impl Into<Thumbnail>) -> Self {
+        let thumbnail = thumbnail.into();
+        self.add_lazy_tile(item, move || Ok(thumbnail))
+    }
+
+    /// Adds a tile whose thumbnail is produced by `loader`.
+    ///
+    /// The loader runs on the background thread once the page is first shown,
+    /// e.g. to read or download the image.
+    pub fn add_lazy_tile<F>(mut self, item: ComObject<ListItem>, loader: F) -> Self
+    where
+        F: Send + FnOnce() -> Result<Thumbnail> + 'static,
+    {
+        self.tiles.push((item, Box::new(loader)));
+        self
+    }
+}
+
+impl GalleryPageBuilder {
+    /// Returns the list page builder with the tiles and their thumbnail loading attached.
+    fn into_list(self) -> ListPageBuilder {
+        let mut list = self
+            .list
+            .grid_properties(GridProperties::from(self.tile_size).into());
+        for (item, _) in self.tiles.iter() {
+            if let Some(placeholder) = &self.placeholder_icon
+                && let Ok(mut icon) = item.icon_mut()
+                && icon.is_none()
+            {
+                *icon = Some(placeholder.clone());
+            }
+            list = list.add_item(item.clone());
+        }
+
+        if !self.tiles.is_empty() {
+            let token = CancellationToken::new();
+            let loader_token = token.clone();
+            let tiles = self.tiles;
+            list = list
+                .cancel_on_drop(token)
+                .on_first_load(move |_| load_thumbnails(tiles, loader_token));
+        }
+        list
+    }
+}
+
+impl ComBuilder for GalleryPageBuilder {
+    type Output = ListPage;
+
+    fn build(self) -> ComObject<ListPage> {
+        self.into_list().build()
+    }
+
+    fn build_unmanaged(self) -> ListPage {
+        self.into_list().build_unmanaged()
+    }
+}
+
+/// Loads the thumbnails of `tiles` on a background thread, until `token` is cancelled.
+///
+/// Items are only held weakly, so dropped items are skipped.
+fn load_thumbnails(tiles: Vec<(ComObject<ListItem>, ThumbnailLoader)>, token: CancellationToken) {
+    let tiles: Vec<(Weak<IListItem>, ThumbnailLoader)> = tiles
+        .into_iter()
+        .filter_map(|(item, loader)| {
+            Some((item.to_interface::<IListItem>().downgrade().ok()?, loader))
+        })
+        .collect();
+    let upgrade = |item: &Weak<IListItem>| {
+        item.upgrade()
+            .and_then(|item| ComObject::<ListItem>::cast_from(&item).ok())
+    };
+    std::thread::spawn(move || {
+        for (item, loader) in tiles {
+            if token.is_cancelled() {
+                return;
+            }
+            if upgrade(&item).is_none() {
+                continue;
+            }
+            match loader().and_then(Thumbnail::load) {
+                Ok(thumbnail) => {
+                    if let Some(item) = upgrade(&item)
+                        && let Ok(mut icon) = item.icon_mut()
+                    {
+                        *icon = Some(thumbnail);
+                    }
+                }
+                Err(e) => LogMessage::warning(
+                    format!("Failed to load gallery thumbnail: {}", e.message()).into(),
+                )
+                .log(),
+            }
+        }
+    });
+}
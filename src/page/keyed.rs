@@ -0,0 +1,172 @@
+//! Keyed reconciliation of [`ListPage`][`super::list::ListPage`] items.
+//!
+//! Instead of replacing every item on each refresh, [`KeyedItems`] matches
+//! new view-models against the existing items by a stable key,
+//! updates matched items in place, and only creates or drops what actually changed.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+
+use crate::details::{Details, Tag};
+use windows::Win32::Foundation::{ERROR_ALREADY_EXISTS, ERROR_LOCK_VIOLATION};
+use windows_core::{ComObject, Error, HSTRING, Result};
+
+use super::list::{ListItem, ListItem_Impl, ListPage_Impl};
+
+/// A view-model of a list item, identified by a stable key.
+pub trait ListItemModel {
+    /// Type of the key which identifies an item across refreshes.
+    type Key: Eq + Hash;
+
+    /// Returns the key of this model.
+    fn key(&self) -> Self::Key;
+
+    /// Creates a list item for a model whose key is not in the list yet.
+    fn build(&self) -> Result<ComObject<ListItem>>;
+
+    /// Returns the title of the item.
+    fn title(&self) -> HSTRING;
+
+    /// Returns the subtitle of the item.
+    fn subtitle(&self) -> HSTRING {
+        HSTRING::new()
+    }
+
+    /// Returns the tags of the item.
+    ///
+    /// Tags are compared by identity, reuse the same [`Tag`] objects
+    /// to avoid notifying the host on every refresh.
+    fn tags(&self) -> Vec<ComObject<Tag>> {
+        Vec::new()
+    }
+
+    /// Returns the details of the item.
+    ///
+    /// Details are compared by identity, like [`ListItemModel::tags`].
+    fn details(&self) -> Option<ComObject<Details>> {
+        None
+    }
+
+    /// Updates an existing item in place.
+    ///
+    /// By default, title, subtitle, tags and details are synchronized,
+    /// and only the properties that differ are written,
+    /// so the host is notified for actual changes only.
+    fn update(&self, item: &ListItem_Impl) -> Result<()> {
+        let title = self.title();
+        if *item.title()? != title {
+            *item.title_mut()? = title;
+        }
+        let subtitle = self.subtitle();
+        if *item.subtitle()? != subtitle {
+            *item.subtitle_mut()? = subtitle;
+        }
+        let tags = self.tags();
+        if !same_objects(&item.tags()?, &tags) {
+            *item.tags_mut()? = tags;
+        }
+        let details = self.details();
+        let details_changed = match (&*item.details()?, &details) {
+            (Some(old), Some(new)) => !std::ptr::eq(old.get(), new.get()),
+            (None, None) => false,
+            _ => true,
+        };
+        if details_changed {
+            *item.details_mut()? = details;
+        }
+        Ok(())
+    }
+}
+
+fn same_objects<T: windows_core::ComObjectInner>(a: &[ComObject<T>], b: &[ComObject<T>]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b.iter())
+            .all(|(x, y)| std::ptr::eq(x.get(), y.get()))
+}
+
+/// Keeps track of the items of a list page by key.
+///
+/// Hold one instance per page, and call [`KeyedItems::reconcile`]
+/// with the fresh view-models whenever the data changes.
+pub struct KeyedItems<K> {
+    items: Mutex<HashMap<K, ComObject<ListItem>>>,
+}
+
+impl<K: Eq + Hash> KeyedItems<K> {
+    /// Creates an empty tracker.
+    pub fn new() -> Self {
+        KeyedItems {
+            items: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reconciles the items of `page` with `models`.
+    ///
+    /// Items whose key is still present are updated in place with [`ListItemModel::update`],
+    /// new keys are built with [`ListItemModel::build`], and missing keys are dropped.
+    /// The resulting order follows `models`.
+    ///
+    /// At most one items-changed notification is emitted,
+    /// and none if the set and order of items is unchanged.
+    ///
+    /// Fails with `ERROR_ALREADY_EXISTS` if two models have the same key.
+    /// On failure, the page and the tracked items are left unchanged,
+    /// except for the items already updated in place.
+    pub fn reconcile<M>(
+        &self,
+        page: &ListPage_Impl,
+        models: impl IntoIterator<Item = M>,
+    ) -> Result<()>
+    where
+        M: ListItemModel<Key = K>,
+    {
+        let mut known = self
+            .items
+            .lock()
+            .map_err(|_| Error::from(ERROR_LOCK_VIOLATION))?;
+        let mut next = HashMap::with_capacity(known.len());
+        let mut ordered = Vec::with_capacity(known.len());
+        for model in models {
+            let key = model.key();
+            if next.contains_key(&key) {
+                return Err(Error::new(
+                    ERROR_ALREADY_EXISTS.to_hresult(),
+                    "Duplicate list item key",
+                ));
+            }
+            let item = match known.get(&key) {
+                Some(item) => {
+                    model.update(item)?;
+                    item.clone()
+                }
+                None => model.build()?,
+            };
+            ordered.push(item.clone());
+            next.insert(key, item);
+        }
+        *known = next;
+        drop(known);
+
+        if !same_objects(&page.items()?, &ordered) {
+            *page.items_mut()? = ordered;
+        }
+        Ok(())
+    }
+
+    /// Forgets all tracked items, so the next reconciliation rebuilds every item.
+    pub fn clear(&self) -> Result<()> {
+        self.items
+            .lock()
+            .map_err(|_| Error::from(ERROR_LOCK_VIOLATION))?
+            .clear();
+        Ok(())
+    }
+}
+
+impl<K: Eq + Hash> Default for KeyedItems<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,18 +1,29 @@
 //! List page which can display a scrollable list of items.
 
 use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use crate::{
     bindings::*,
-    cmd_item::{CommandItem, CommandItem_Impl},
+    cancel::CancellationToken,
+    cmd::{BaseCommandBuilder, InvokableCommand, InvokableCommandBuilder},
+    cmd_item::{CommandItem, CommandItemBuilder, CommandItem_Impl},
+    cmd_result::CommandResult,
     details::{Details, Tag},
-    filter::Filters,
+    error::catch_panic,
+    filter::{Filters, Filters_Impl},
+    frecency::Frecency,
+    icon::{IconData, IconInfo},
     notify::*,
-    utils::{ComBuilder, GridProperties, OkOrEmpty, assert_send_sync, map_array},
+    utils::{ArrayCache, ComBuilder, GridProperties, OkOrEmpty, assert_send_sync, map_array},
+};
+use windows_core::{
+    ComObject, Error, IInspectable, IUnknownImpl as _, Interface as _, Result, implement,
 };
-use windows_core::{ComObject, IInspectable, IUnknownImpl as _, Result, implement};
 use windows_core::HSTRING;
 
+use super::paged::{PagedDriver, PagedSource};
 use super::{BasePage, BasePage_Impl};
 
 /// Represents a single item in a list.
@@ -133,8 +144,11 @@ impl ListItem_Impl {
     ///
     /// Notifies the host about the change when dropping the guard.
     pub fn tags_mut(&self) -> Result<NotifyLockWriteGuard<'_, Vec<ComObject<Tag>>>> {
-        self.tags
-            .write(|| self.base.emit_prop_changed(&self.to_interface(), "Tags"))
+        self.tags.write(|| {
+            // Filter predicates may look at the tags, so pages must re-filter like for a new title.
+            self.base.bump_text_generation();
+            self.base.emit_prop_changed(&self.to_interface(), "Tags")
+        })
     }
 
     /// Readonly access to [`IListItem::Section`].
@@ -214,6 +228,12 @@ impl ICommandItem_Impl for ListItem_Impl {
     }
 }
 
+impl Observable for ListItem {
+    fn is_observed(this: &ListItem_Impl) -> bool {
+        CommandItem::is_observed(&this.base)
+    }
+}
+
 impl INotifyPropChanged_Impl for ListItem_Impl {
     fn PropChanged(
         &self,
@@ -233,6 +253,8 @@ impl INotifyPropChanged_Impl for ListItem_Impl {
     }
 }
 
+type FirstLoadHook = Box<dyn Send + FnOnce(&ListPage_Impl)>;
+
 /// Represents a page that displays a list of items.
 ///
 /// See [`ListPage_Impl`] for field accessors.
@@ -242,21 +264,37 @@ impl INotifyPropChanged_Impl for ListItem_Impl {
 pub struct ListPage {
     pub base: ComObject<BasePage>,
     empty_content: NotifyLock<Option<ComObject<CommandItem>>>,
+    error_content: NotifyLock<Option<ComObject<CommandItem>>>,
+    no_results: Option<ComObject<CommandItem>>,
+    type_to_search: Option<ComObject<CommandItem>>,
+    show_errors: bool,
+    match_search: bool,
+    usage: Option<Arc<Frecency>>,
+    usage_generation: AtomicU64,
+    text_generation: AtomicU64,
     filters: NotifyLock<Option<ComObject<Filters>>>,
     items: NotifyLock<Vec<ComObject<ListItem>>>,
+    items_cache: ArrayCache<IListItem, ComObject<ListItem>>,
     grid_properties: NotifyLock<Option<ComObject<GridProperties>>>,
     placeholder: NotifyLock<HSTRING>,
     search_text: NotifyLock<HSTRING>,
     has_more: NotifyLock<bool>,
     more_fn: Box<dyn Send + Sync + Fn(&ListPage_Impl) -> Result<()>>,
     show_details: NotifyLock<bool>,
-    item_event: ItemsChangedEventHandler,
+    first_load: Mutex<Option<FirstLoadHook>>,
+    drop_token: Option<CancellationToken>,
+    item_event: TrackedEvent<ItemsChangedEventHandler>,
 }
 
 /// Builder for [`ListPage`].
 pub struct ListPageBuilder {
     base: ComObject<BasePage>,
     empty_content: Option<ComObject<CommandItem>>,
+    no_results: Option<ComObject<CommandItem>>,
+    type_to_search: Option<ComObject<CommandItem>>,
+    show_errors: Option<bool>,
+    match_search: bool,
+    usage: Option<Arc<Frecency>>,
     filters: Option<ComObject<Filters>>,
     grid_properties: Option<ComObject<GridProperties>>,
     items: Vec<ComObject<ListItem>>,
@@ -264,6 +302,8 @@ pub struct ListPageBuilder {
     search_text: Option<HSTRING>,
     more_fn: Option<Box<dyn Send + Sync + Fn(&ListPage_Impl) -> Result<()>>>,
     show_details: Option<bool>,
+    first_load: Option<FirstLoadHook>,
+    drop_token: Option<CancellationToken>,
 }
 
 impl ListPageBuilder {
@@ -272,6 +312,11 @@ impl ListPageBuilder {
         ListPageBuilder {
             base,
             empty_content: None,
+            no_results: None,
+            type_to_search: None,
+            show_errors: None,
+            match_search: false,
+            usage: None,
             filters: None,
             items: Vec::new(),
             grid_properties: None,
@@ -279,6 +324,8 @@ impl ListPageBuilder {
             search_text: None,
             more_fn: None,
             show_details: None,
+            first_load: None,
+            drop_token: None,
         }
     }
 
@@ -288,12 +335,67 @@ impl ListPageBuilder {
         self
     }
 
+    /// Sets the empty content shown when the search text is not empty.
+    ///
+    /// Falls back to [`ListPageBuilder::empty_content`] if not set.
+    pub fn no_results(mut self, no_results: ComObject<CommandItem>) -> Self {
+        self.no_results = Some(no_results);
+        self
+    }
+
+    /// Sets the empty content shown when the search text is empty,
+    /// e.g. to prompt the user to type in a `DynamicListPage`.
+    ///
+    /// Falls back to [`ListPageBuilder::empty_content`] if not set.
+    pub fn type_to_search(mut self, type_to_search: ComObject<CommandItem>) -> Self {
+        self.type_to_search = Some(type_to_search);
+        self
+    }
+
+    /// Sets whether errors of [`ListPageBuilder::more_fn`] are shown to the user.
+    ///
+    /// If enabled, a failed [`IListPage::LoadMore`] shows the error as the empty content of the page,
+    /// with a command to retry it, instead of returning the error to the host.
+    /// See [`ListPage_Impl::show_error`] for details.
+    pub fn show_errors(mut self, show_errors: bool) -> Self {
+        self.show_errors = Some(show_errors);
+        self
+    }
+
     /// Sets the filters for the list page.
+    ///
+    /// The predicate of the selected filter, if any, is applied to the items automatically.
+    /// See [`FiltersBuilder::predicate`][`crate::filter::FiltersBuilder::predicate`].
+    ///
+    /// The filters are attached to the page by [`ComBuilder::build`],
+    /// a page built with [`ComBuilder::build_unmanaged`] doesn't react to filter changes.
     pub fn filters(mut self, filters: ComObject<Filters>) -> Self {
         self.filters = Some(filters);
         self
     }
 
+    /// Sets whether the page hides items not matching the search text.
+    ///
+    /// If enabled, only items whose title or subtitle contain the search text, ignoring case,
+    /// are returned to the host, together with the selected filter.
+    /// Useful for a `DynamicListPage` which filters a fixed set of items.
+    /// Command Palette already matches search text for other list pages.
+    pub fn match_search(mut self, match_search: bool) -> Self {
+        self.match_search = match_search;
+        self
+    }
+
+    /// Sets a [`Frecency`] tracker ranking the items of the page, most used first.
+    ///
+    /// Items are ranked by the usage recorded for the [`ICommand::Id`] of their command,
+    /// after the selected filter and the search text are applied.
+    /// Items without recorded usage keep their order after used items.
+    /// The ranking is updated the next time the host gets the items after usage is recorded.
+    pub fn rank_by_usage(mut self, frecency: Arc<Frecency>) -> Self {
+        self.usage = Some(frecency);
+        self
+    }
+
     /// Sets the items for the list page.
     pub fn items(mut self, items: Vec<ComObject<ListItem>>) -> Self {
         self.items = items;
@@ -335,21 +437,72 @@ impl ListPageBuilder {
         self
     }
 
+    /// Sets a [`PagedSource`] to load items from.
+    ///
+    /// The source replaces any function set by [`ListPageBuilder::more_fn`].
+    /// Each call to [`IListPage::LoadMore`] fetches the next page and appends it to the items,
+    /// keeping [`IListPage::HasMoreItems`] and [`IPage::IsLoading`] up to date.
+    /// Calls made while a fetch is in progress are ignored.
+    ///
+    /// This also enables [`ListPageBuilder::show_errors`] unless it is set explicitly,
+    /// so a failed fetch is shown to the user and can be retried.
+    ///
+    /// The first page is fetched on the first `LoadMore` call,
+    /// call [`IListPage_Impl::LoadMore`] on the built page to load it eagerly.
+    pub fn paged_source<S: PagedSource>(mut self, source: S) -> Self {
+        let driver = PagedDriver::new(source);
+        self.show_errors.get_or_insert(true);
+        self.more_fn(move |page| driver.load_more(page))
+    }
+
     /// Sets whether to show details for each item in the list.
     pub fn show_details(mut self, show_details: bool) -> Self {
         self.show_details = Some(show_details);
         self
     }
+
+    /// Sets a hook called the first time the host gets the items, i.e. when the page is first shown.
+    pub(crate) fn on_first_load<F>(mut self, hook: F) -> Self
+    where
+        F: Send + FnOnce(&ListPage_Impl) + 'static,
+    {
+        self.first_load = Some(Box::new(hook));
+        self
+    }
+
+    /// Sets a token cancelled when the page is dropped, to stop work done on its behalf.
+    pub(crate) fn cancel_on_drop(mut self, token: CancellationToken) -> Self {
+        self.drop_token = Some(token);
+        self
+    }
 }
 
 impl ComBuilder for ListPageBuilder {
     type Output = ListPage;
+
+    fn build(self) -> ComObject<ListPage> {
+        let page: ComObject<ListPage> = self.build_unmanaged().into();
+        let _ = page.adopt_filters();
+        crate::cmd::registered(page)
+    }
+
     fn build_unmanaged(self) -> ListPage {
         ListPage {
             base: self.base,
             empty_content: NotifyLock::new(self.empty_content),
+            error_content: NotifyLock::new(None),
+            no_results: self.no_results,
+            type_to_search: self.type_to_search,
+            show_errors: self.show_errors.unwrap_or(false),
+            match_search: self.match_search,
+            usage_generation: AtomicU64::new(
+                self.usage.as_ref().map(|u| u.generation()).unwrap_or(0),
+            ),
+            usage: self.usage,
+            text_generation: AtomicU64::new(ListPage::items_text_generation(&self.items)),
             filters: NotifyLock::new(self.filters),
             items: NotifyLock::new(self.items),
+            items_cache: ArrayCache::new(),
             grid_properties: NotifyLock::new(self.grid_properties),
             placeholder: NotifyLock::new(self.placeholder.unwrap_or_else(|| HSTRING::new())),
             search_text: NotifyLock::new(self.search_text.unwrap_or_else(|| HSTRING::new())),
@@ -362,7 +515,17 @@ impl ComBuilder for ListPageBuilder {
                 })
             }),
             show_details: NotifyLock::new(self.show_details.unwrap_or(false)),
-            item_event: ItemsChangedEventHandler::new(),
+            first_load: Mutex::new(self.first_load),
+            drop_token: self.drop_token,
+            item_event: TrackedEvent::new(),
+        }
+    }
+}
+
+impl Drop for ListPage {
+    fn drop(&mut self) {
+        if let Some(token) = &self.drop_token {
+            token.cancel();
         }
     }
 }
@@ -375,6 +538,17 @@ impl Deref for ListPage {
     }
 }
 
+impl ListPage {
+    /// Returns a number which changes whenever the texts of any of `items` change.
+    ///
+    /// See [`CommandItem_Impl::text_generation`].
+    fn items_text_generation(items: &[ComObject<ListItem>]) -> u64 {
+        items
+            .iter()
+            .fold(0, |sum, x| sum.wrapping_add(x.base.text_generation()))
+    }
+}
+
 impl ListPage_Impl {
     pub(crate) fn emit_self_items_changed(&self, index: i32) {
         let sender: IInspectable = self.to_interface();
@@ -432,6 +606,62 @@ impl ListPage_Impl {
         })
     }
 
+    /// Shows `error` to the user in place of the empty content of the page.
+    ///
+    /// The error is shown as a [`CommandItem`] with an error icon, `title` describing what failed,
+    /// e.g. `"Failed to load items"`, and the error message as subtitle,
+    /// whose command is `retry` if given. It takes precedence over any other empty content,
+    /// until [`ListPage_Impl::clear_error`] is called.
+    ///
+    /// Like any empty content, it is only visible while the page has no items.
+    pub fn show_error(
+        &self,
+        title: impl Into<HSTRING>,
+        error: &Error,
+        retry: Option<ComObject<InvokableCommand>>,
+    ) -> Result<()> {
+        let cmd = match retry {
+            Some(retry) => retry,
+            None => InvokableCommandBuilder::new(BaseCommandBuilder::new().build()).build(),
+        };
+        let item = CommandItemBuilder::try_new(cmd.to_interface())?
+            .icon(IconInfo::new(IconData::from("\u{E783}")))
+            .title(title)
+            .subtitle(error.message())
+            .build();
+        *self.error_content.write(|| {
+            self.base
+                .base
+                .emit_prop_changed(self.to_interface(), "EmptyContent")
+        })? = Some(item);
+        Ok(())
+    }
+
+    /// Removes the error shown by [`ListPage_Impl::show_error`], if any.
+    pub fn clear_error(&self) -> Result<()> {
+        if self.error_content.read()?.is_none() {
+            return Ok(());
+        }
+        *self.error_content.write(|| {
+            self.base
+                .base
+                .emit_prop_changed(self.to_interface(), "EmptyContent")
+        })? = None;
+        Ok(())
+    }
+
+    fn load_more_retry(&self) -> Result<ComObject<InvokableCommand>> {
+        let weak = self.to_interface::<IListPage>().downgrade()?;
+        Ok(retry_command(move || {
+            if let Some(page) = weak.upgrade() {
+                let page = ComObject::<ListPage>::cast_from(&page)?;
+                *page.has_more_mut()? = true;
+                page.LoadMore()?;
+            }
+            Ok(())
+        }))
+    }
+
     /// Readonly access to [`IListPage::Filters`].
     ///
     #[doc = include_str!("../bindings_docs/IListPage/Filters.md")]
@@ -442,14 +672,25 @@ impl ListPage_Impl {
     /// Mutable access to [`IListPage::Filters`].
     ///
     #[doc = include_str!("../bindings_docs/IListPage/Filters.md")]
+    ///
+    /// Notifies the host about the change when dropping the guard.
     pub fn filters_mut(&self) -> Result<NotifyLockWriteGuard<'_, Option<ComObject<Filters>>>> {
         self.filters.write(|| {
+            let _ = self.adopt_filters();
             self.base
                 .base
                 .emit_prop_changed(self.to_interface(), "Filters")
         })
     }
 
+    /// Makes the filters of the page notify it when their selection or list changes.
+    pub(crate) fn adopt_filters(&self) -> Result<()> {
+        if let Some(filters) = self.filters.read()?.as_ref() {
+            filters.set_owner(&self.to_interface())?;
+        }
+        Ok(())
+    }
+
     /// Readonly access to [`IListPage::GetItems`].
     ///
     #[doc = include_str!("../bindings_docs/IListPage/GetItems.md")]
@@ -463,8 +704,126 @@ impl ListPage_Impl {
     ///
     /// Notifies the host about the change when dropping the guard.
     pub fn items_mut(&self) -> Result<NotifyLockWriteGuard<'_, Vec<ComObject<ListItem>>, usize>> {
-        self.items
-            .write_with_peek(|v| v.len(), |len| self.emit_self_items_changed(len as i32))
+        self.items.write_with_peek(
+            |v| {
+                self.items_cache.invalidate();
+                v.len()
+            },
+            |len| {
+                self.emit_self_items_changed(len as i32);
+                self.emit_filter_counts();
+            },
+        )
+    }
+
+    fn search_needle(&self) -> Option<String> {
+        if !self.match_search {
+            return None;
+        }
+        let search = self.search_text.read().ok()?;
+        (!search.is_empty()).then(|| search.to_string_lossy().to_lowercase())
+    }
+
+    fn matches_search(item: &ListItem_Impl, needle: Option<&str>) -> bool {
+        let Some(needle) = needle else {
+            return true;
+        };
+        let contains = |text: Result<NotifyLockReadGuard<'_, HSTRING>>| {
+            text.is_ok_and(|t| t.to_string_lossy().to_lowercase().contains(needle))
+        };
+        contains(item.title()) || contains(item.subtitle())
+    }
+
+    fn usage_score(usage: &Frecency, item: &ListItem_Impl) -> f64 {
+        item.command()
+            .and_then(|command| command.resolve()?.Id())
+            .map(|id| usage.score(&id.to_string_lossy()))
+            .unwrap_or(0.0)
+    }
+
+    /// Returns a snapshot of the filters, without holding the filters lock afterwards.
+    ///
+    /// The items lock is always taken before the filters lock, never the other way round.
+    fn filters_snapshot(&self) -> Option<ComObject<Filters>> {
+        self.filters.read().ok().and_then(|f| f.clone())
+    }
+
+    fn visible_items<'a>(&self, items: &'a [ComObject<ListItem>]) -> Vec<&'a ComObject<ListItem>> {
+        let filters = self.filters_snapshot();
+        let current = filters
+            .as_ref()
+            .and_then(|f| f.current().ok().flatten())
+            .map(|f| f.id.clone());
+        let needle = self.search_needle();
+        let mut visible: Vec<_> = items
+            .iter()
+            .filter(|x| {
+                filters
+                    .as_ref()
+                    .zip(current.as_ref())
+                    .is_none_or(|(f, id)| f.matches(id, x))
+                    && Self::matches_search(x, needle.as_deref())
+            })
+            .collect();
+        if let Some(usage) = &self.usage {
+            visible.sort_by_cached_key(|x| {
+                // Bits of non-negative floats order the same as their values.
+                std::cmp::Reverse(Self::usage_score(usage, x).max(0.0).to_bits())
+            });
+        }
+        visible
+    }
+
+    /// Updates the match counts of `filters`, returning whether any of them changed.
+    ///
+    /// Must not be called while holding the filters lock, see [`ListPage_Impl::filters_snapshot`].
+    fn count_filters(&self, filters: &Filters_Impl) -> Result<bool> {
+        let items = self.items.read()?;
+        let needle = self.search_needle();
+        filters.update_counts(|passes| {
+            items
+                .iter()
+                .filter(|x| passes(x) && Self::matches_search(x, needle.as_deref()))
+                .count()
+        })
+    }
+
+    fn emit_filter_counts(&self) {
+        let filters = self.filters_snapshot();
+        if let Some(filters) = filters
+            && self.count_filters(&filters).unwrap_or(false)
+        {
+            self.base
+                .base
+                .emit_prop_changed(self.to_interface(), "Filters");
+        }
+    }
+
+    /// Recomputes the visible items after the selected filter or the search text changed.
+    pub(crate) fn refilter(&self) {
+        // Invalidate under the items lock, so a concurrent `GetItems` can't cache a stale view.
+        let Ok(items) = self.items.write(|| {}) else {
+            return;
+        };
+        self.items_cache.invalidate();
+        let len = items.len();
+        drop(items);
+        self.emit_self_items_changed(len as i32);
+    }
+
+    /// Notifies the host about everything depending on the search text, after it changed from `old` to `new`.
+    pub(crate) fn search_text_changed(&self, old: &HSTRING, new: &HSTRING) {
+        if (self.no_results.is_some() || self.type_to_search.is_some())
+            && old.is_empty() != new.is_empty()
+        {
+            self.base
+                .base
+                .emit_prop_changed(self.to_interface(), "EmptyContent");
+        }
+        if self.match_search {
+            self.refilter();
+            self.emit_filter_counts();
+        }
     }
 
     /// Readonly access to [`IListPage::GridProperties`].
@@ -553,6 +912,17 @@ impl ListPage_Impl {
 
 impl IListPage_Impl for ListPage_Impl {
     fn EmptyContent(&self) -> windows_core::Result<ICommandItem> {
+        if let Some(error) = self.error_content.read()?.as_ref() {
+            return Ok(error.to_interface());
+        }
+        let state = if self.search_text.read()?.is_empty() {
+            &self.type_to_search
+        } else {
+            &self.no_results
+        };
+        if let Some(state) = state {
+            return Ok(state.to_interface());
+        }
         self.empty_content
             .read()?
             .as_ref()
@@ -561,15 +931,44 @@ impl IListPage_Impl for ListPage_Impl {
     }
 
     fn Filters(&self) -> windows_core::Result<IFilters> {
-        self.filters
-            .read()?
-            .as_ref()
-            .map(|f| f.to_interface())
-            .ok_or_empty()
+        let filters = self.filters.read()?.clone();
+        if let Some(filters) = filters.as_ref() {
+            catch_panic(|| self.count_filters(filters))?;
+        }
+        filters.as_ref().map(|f| f.to_interface()).ok_or_empty()
     }
 
     fn GetItems(&self) -> windows_core::Result<windows_core::Array<IListItem>> {
-        Ok(map_array(&self.items.read()?, |x| Some(x.to_interface())))
+        let first_load = self.first_load.lock().ok().and_then(|mut hook| hook.take());
+        if let Some(hook) = first_load {
+            catch_panic(|| {
+                hook(self);
+                Ok(())
+            })?;
+        }
+        let items = self.items.read()?;
+        if let Some(usage) = &self.usage {
+            let generation = usage.generation();
+            if self.usage_generation.swap(generation, Ordering::AcqRel) != generation {
+                self.items_cache.invalidate();
+            }
+        }
+        // Items edited in place may match the search or the filters differently now.
+        let generation = ListPage::items_text_generation(&items);
+        let edited = self.text_generation.swap(generation, Ordering::AcqRel) != generation;
+        if edited {
+            self.items_cache.invalidate();
+        }
+        let result = catch_panic(|| {
+            Ok(self
+                .items_cache
+                .get_or_init(|| self.visible_items(&items), |x| Some(x.to_interface())))
+        });
+        drop(items);
+        if edited {
+            self.emit_filter_counts();
+        }
+        result
     }
 
     fn GridProperties(&self) -> windows_core::Result<IGridProperties> {
@@ -585,7 +984,14 @@ impl IListPage_Impl for ListPage_Impl {
     }
 
     fn LoadMore(&self) -> windows_core::Result<()> {
-        (self.more_fn)(self)
+        match catch_panic(|| (self.more_fn)(self)) {
+            Ok(()) => self.clear_error(),
+            Err(e) if self.show_errors => {
+                *self.has_more_mut()? = false;
+                self.show_error("Failed to load items", &e, Some(self.load_more_retry()?))
+            }
+            Err(e) => Err(e),
+        }
     }
 
     fn PlaceholderText(&self) -> windows_core::Result<windows_core::HSTRING> {
@@ -629,6 +1035,12 @@ impl ICommand_Impl for ListPage_Impl {
     }
 }
 
+impl Observable for ListPage {
+    fn is_observed(this: &ListPage_Impl) -> bool {
+        this.item_event.has_handlers() || BasePage::is_observed(&this.base)
+    }
+}
+
 impl INotifyPropChanged_Impl for ListPage_Impl {
     fn PropChanged(
         &self,
@@ -668,4 +1080,78 @@ impl INotifyItemsChanged_Impl for ListPage_Impl {
     }
 }
 
+/// Creates a "Retry" command running `retry`, for use with [`ListPage_Impl::show_error`].
+pub(crate) fn retry_command<F>(retry: F) -> ComObject<InvokableCommand>
+where
+    F: Send + Sync + Fn() -> Result<()> + 'static,
+{
+    let base = BaseCommandBuilder::new()
+        .name("Retry")
+        .icon(IconInfo::new(IconData::from("\u{E72C}")))
+        .build();
+    InvokableCommandBuilder::new(base)
+        .anon_func(move || {
+            retry()?;
+            Ok(CommandResult::KeepOpen)
+        })
+        .build()
+}
+
 const _: () = assert_send_sync::<ComObject<ListPage>>();
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::{Filter, FiltersBuilder};
+    use crate::page::BasePageBuilder;
+    use windows::Win32::System::Com::{COINIT_MULTITHREADED, CoInitializeEx};
+
+    fn item(title: &str) -> ComObject<ListItem> {
+        let command = BaseCommandBuilder::new().build();
+        let base = CommandItemBuilder::try_new(command.to_interface())
+            .unwrap()
+            .title(title)
+            .build();
+        ListItemBuilder::new(base).build()
+    }
+
+    fn filter_names(filters: &Filters_Impl) -> Vec<String> {
+        filters
+            .Filters()
+            .unwrap()
+            .iter()
+            .flatten()
+            .map(|f| f.cast::<IFilter>().unwrap().Name().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn renamed_items_are_refiltered() {
+        let _ = unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) };
+        let filters = FiltersBuilder::new()
+            .add_filter(ComObject::new(Filter::new("all", "All")))
+            .add_filter(ComObject::new(Filter::new("long", "Long")))
+            .predicate("long", |item| item.title().is_ok_and(|t| t.len() > 3))
+            .show_counts(true)
+            .build();
+        let renamed = item("bar");
+        let base = BasePageBuilder::new(BaseCommandBuilder::new().build()).build();
+        let page = ListPageBuilder::new(base)
+            .items(vec![item("foo"), renamed.clone()])
+            .filters(filters.clone())
+            .match_search(true)
+            .search_text("foo")
+            .build();
+        assert_eq!(page.GetItems().unwrap().len(), 1);
+        page.Filters().unwrap();
+        assert_eq!(filter_names(&filters), ["All (1)", "Long (0)"]);
+
+        *renamed.title_mut().unwrap() = "foobar".into();
+        assert_eq!(page.GetItems().unwrap().len(), 2);
+        assert_eq!(filter_names(&filters), ["All (2)", "Long (1)"]);
+
+        *renamed.title_mut().unwrap() = "baz".into();
+        assert_eq!(page.GetItems().unwrap().len(), 1);
+        assert_eq!(filter_names(&filters), ["All (1)", "Long (0)"]);
+    }
+}
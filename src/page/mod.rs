@@ -2,9 +2,15 @@
 //! 
 //! When selecting a command item that contains a page, the page will be displayed.
 
+pub mod autocomplete;
 pub mod content;
 pub mod dyn_list;
+pub mod gallery;
+pub mod keyed;
 pub mod list;
+pub mod paged;
+pub mod router;
+pub mod section;
 
 use std::ops::Deref;
 
@@ -119,7 +125,7 @@ impl BasePage_Impl {
     pub fn loading_mut(&self) -> Result<NotifyLockWriteGuard<'_, bool>> {
         self.loading.write(|| {
             self.base
-                .emit_prop_changed(self.to_interface(), "Loading")
+                .emit_prop_changed(self.to_interface(), "IsLoading")
         })
     }
 
@@ -169,6 +175,12 @@ impl ICommand_Impl for BasePage_Impl {
     }
 }
 
+impl Observable for BasePage {
+    fn is_observed(this: &BasePage_Impl) -> bool {
+        BaseCommand::is_observed(&this.base)
+    }
+}
+
 impl INotifyPropChanged_Impl for BasePage_Impl {
     fn PropChanged(
         &self,
@@ -0,0 +1,240 @@
+//! Paged data sources which can drive [`IListPage::LoadMore`][`crate::bindings::IListPage::LoadMore`] of a [`ListPage`][`super::list::ListPage`].
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+use windows_core::{ComObject, Result};
+
+use super::list::{ListItem, ListPage_Impl};
+
+/// A single page of items fetched from a [`PagedSource`].
+pub struct Page<T, C> {
+    /// Items of this page, appended to the list in order.
+    pub items: Vec<T>,
+    /// Cursor pointing at the next page, `None` if this is the last page.
+    pub next: Option<C>,
+}
+
+impl<T, C> Page<T, C> {
+    /// Creates a page which is followed by the page at `next`.
+    pub fn new(items: Vec<T>, next: C) -> Self {
+        Page {
+            items,
+            next: Some(next),
+        }
+    }
+
+    /// Creates the last page of a source.
+    pub fn last(items: Vec<T>) -> Self {
+        Page { items, next: None }
+    }
+}
+
+/// A data source which yields list items page by page.
+///
+/// Attach it to a list page with [`ListPageBuilder::paged_source`][`super::list::ListPageBuilder::paged_source`].
+pub trait PagedSource: Send + Sync + 'static {
+    /// Type of the items yielded by this source.
+    type Item: Into<ComObject<ListItem>>;
+    /// Opaque position of a page, e.g. an offset or a continuation token.
+    type Cursor: Send + 'static;
+
+    /// Fetches the page at `cursor`.
+    ///
+    /// `cursor` is `None` for the first page, and the [`Page::next`]
+    /// of the previously fetched page afterwards.
+    /// If fetching fails, the same cursor is passed again on retry.
+    fn fetch(&self, cursor: Option<&Self::Cursor>) -> Result<Page<Self::Item, Self::Cursor>>;
+}
+
+enum CursorState<C> {
+    Start,
+    Next(C),
+    Done,
+}
+
+/// Runs a function when dropped, so state set for a fetch is reset even if the source panics.
+struct ResetOnDrop<F: FnMut()>(F);
+
+impl<F: FnMut()> Drop for ResetOnDrop<F> {
+    fn drop(&mut self) {
+        (self.0)()
+    }
+}
+
+/// Drives a [`PagedSource`] on behalf of a list page.
+pub(crate) struct PagedDriver<S: PagedSource> {
+    source: S,
+    cursor: Mutex<CursorState<S::Cursor>>,
+    busy: AtomicBool,
+}
+
+impl<S: PagedSource> PagedDriver<S> {
+    pub(crate) fn new(source: S) -> Self {
+        PagedDriver {
+            source,
+            cursor: Mutex::new(CursorState::Start),
+            busy: AtomicBool::new(false),
+        }
+    }
+
+    /// Fetches the next page and appends it to `page`.
+    ///
+    /// Calls arriving while a fetch is in progress are ignored.
+    pub(crate) fn load_more(&self, page: &ListPage_Impl) -> Result<()> {
+        if self.busy.swap(true, Ordering::AcqRel) {
+            return Ok(());
+        }
+        let _busy = ResetOnDrop(|| self.busy.store(false, Ordering::Release));
+        self.fetch_next(page)
+    }
+
+    fn fetch_next(&self, page: &ListPage_Impl) -> Result<()> {
+        // A panicking fetch leaves the cursor unchanged, so the lock can be recovered.
+        let mut state = self.cursor.lock().unwrap_or_else(PoisonError::into_inner);
+        let cursor = match &*state {
+            CursorState::Start => None,
+            CursorState::Next(cursor) => Some(cursor),
+            CursorState::Done => {
+                drop(state);
+                *page.has_more_mut()? = false;
+                return Ok(());
+            }
+        };
+
+        *page.loading_mut()? = true;
+        let loading = ResetOnDrop(|| {
+            if let Ok(mut loading) = page.loading_mut() {
+                *loading = false;
+            }
+        });
+        let fetched = self.source.fetch(cursor);
+        drop(loading);
+
+        // Keep the cursor on failure, so the page is fetched again on retry.
+        let fetched = fetched?;
+        let has_more = fetched.next.is_some();
+        *state = match fetched.next {
+            Some(next) => CursorState::Next(next),
+            None => CursorState::Done,
+        };
+        drop(state);
+        page.items_mut()?
+            .extend(fetched.items.into_iter().map(Into::into));
+        *page.has_more_mut()? = has_more;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::{IListPage_Impl, IPage_Impl};
+    use crate::cmd::BaseCommandBuilder;
+    use crate::cmd_item::CommandItemBuilder;
+    use crate::page::BasePageBuilder;
+    use crate::page::list::{ListItemBuilder, ListPage, ListPageBuilder};
+    use crate::utils::ComBuilder;
+    use std::sync::Arc;
+    use windows::Win32::Foundation::E_FAIL;
+    use windows::Win32::System::Com::{COINIT_MULTITHREADED, CoInitializeEx};
+    use windows_core::Error;
+
+    enum Outcome {
+        Fail,
+        Panic,
+    }
+
+    /// Yields pages of two items up to `len`, failing or panicking once if asked to.
+    struct Numbers {
+        len: usize,
+        next_outcome: Mutex<Option<Outcome>>,
+        fetched: Mutex<Vec<Option<usize>>>,
+    }
+
+    impl PagedSource for Arc<Numbers> {
+        type Item = ComObject<ListItem>;
+        type Cursor = usize;
+
+        fn fetch(&self, cursor: Option<&usize>) -> Result<Page<Self::Item, usize>> {
+            self.fetched.lock().unwrap().push(cursor.copied());
+            let outcome = self.next_outcome.lock().unwrap().take();
+            match outcome {
+                Some(Outcome::Fail) => return Err(Error::from(E_FAIL)),
+                Some(Outcome::Panic) => panic!("fetch panicked"),
+                None => {}
+            }
+            let start = cursor.copied().unwrap_or(0);
+            let end = (start + 2).min(self.len);
+            let items = (start..end).map(|i| item(&i.to_string())).collect();
+            Ok(if end < self.len {
+                Page::new(items, end)
+            } else {
+                Page::last(items)
+            })
+        }
+    }
+
+    fn item(title: &str) -> ComObject<ListItem> {
+        let command = BaseCommandBuilder::new().build();
+        let base = CommandItemBuilder::try_new(command.to_interface())
+            .unwrap()
+            .title(title)
+            .build();
+        ListItemBuilder::new(base).build()
+    }
+
+    fn paged_page(len: usize) -> (Arc<Numbers>, ComObject<ListPage>) {
+        let _ = unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) };
+        let source = Arc::new(Numbers {
+            len,
+            next_outcome: Mutex::new(None),
+            fetched: Mutex::new(Vec::new()),
+        });
+        let base = BasePageBuilder::new(BaseCommandBuilder::new().build())
+            .loading(false)
+            .build();
+        let page = ListPageBuilder::new(base)
+            .paged_source(source.clone())
+            .show_errors(false)
+            .build();
+        (source, page)
+    }
+
+    #[test]
+    fn loads_pages_until_the_last() {
+        let (source, page) = paged_page(5);
+        for len in [2, 4, 5] {
+            assert!(page.HasMoreItems().unwrap());
+            page.LoadMore().unwrap();
+            assert_eq!(page.items().unwrap().len(), len);
+        }
+        assert!(!page.HasMoreItems().unwrap());
+        page.LoadMore().unwrap();
+        assert_eq!(page.items().unwrap().len(), 5);
+        assert_eq!(*source.fetched.lock().unwrap(), [None, Some(2), Some(4)]);
+    }
+
+    #[test]
+    fn retries_failed_page() {
+        let (source, page) = paged_page(5);
+        page.LoadMore().unwrap();
+        *source.next_outcome.lock().unwrap() = Some(Outcome::Fail);
+        assert_eq!(page.LoadMore().unwrap_err().code(), E_FAIL);
+        assert!(!page.IsLoading().unwrap());
+        page.LoadMore().unwrap();
+        assert_eq!(page.items().unwrap().len(), 4);
+        assert_eq!(*source.fetched.lock().unwrap(), [None, Some(2), Some(2)]);
+    }
+
+    #[test]
+    fn recovers_from_panicking_fetch() {
+        let (source, page) = paged_page(5);
+        *source.next_outcome.lock().unwrap() = Some(Outcome::Panic);
+        assert!(page.LoadMore().is_err());
+        assert!(!page.IsLoading().unwrap());
+        page.LoadMore().unwrap();
+        assert_eq!(page.items().unwrap().len(), 2);
+        assert_eq!(*source.fetched.lock().unwrap(), [None, None]);
+    }
+}
@@ -0,0 +1,368 @@
+//! Navigation to pages by id, with pages built on demand.
+//!
+//! A [`PageRouter`] maps page ids to page factories through patterns like `issue/{id}`.
+//! Attach it to a provider with [`CommandProviderBuilder::router`][`crate::cmd_provider::CommandProviderBuilder::router`],
+//! so `GoToPage` results and deep links resolve to pages built when they are first visited.
+//!
+//! Describe the pages of an extension with [`RoutePattern`]s, and navigate with [`Route`]s created from them
+//! by [`CommandResult::go_to`][`crate::cmd::CommandResult::go_to`], instead of writing page ids by hand:
+//!
+//! ```ignore
+//! let issues = RoutePattern::new("issues");
+//! let issue = RoutePattern::new("issue/{id}");
+//!
+//! let router = PageRouter::new()
+//!     .route(&issues, |_| Ok(issues_page()?.to_interface()))
+//!     .route(&issue, |params| Ok(issue_page(params.parse("id")?)?.to_interface()));
+//!
+//! // In a command holding a clone of `issue`:
+//! CommandResult::go_to(&issue.at(&[&42])?)
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, PoisonError, RwLock};
+
+use crate::bindings::ICommand;
+use windows::Win32::Foundation::{ERROR_BAD_ARGUMENTS, ERROR_LOCK_VIOLATION, ERROR_NOT_FOUND};
+use windows_core::{Error, HSTRING, Interface as _, Result, Weak};
+
+/// A typed page location, created from a [`RoutePattern`] with [`RoutePattern::at`].
+///
+/// The [`PageRouter`] of the pattern resolves the route to a page built by the factory of that pattern.
+#[derive(Debug, Clone)]
+pub struct Route {
+    path: String,
+}
+
+impl Route {
+    /// Returns the page id of the route, e.g. `issue/42`.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+/// Parameters captured from a page id by a route pattern.
+#[derive(Debug, Clone, Default)]
+pub struct RouteParams {
+    path: String,
+    params: Vec<(String, String)>,
+}
+
+impl RouteParams {
+    /// Returns the whole page id, e.g. `issue/42`.
+    ///
+    /// Use it as the id of the built page, so the host can find it again.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the value captured by `{name}`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Parses the value captured by `{name}`.
+    ///
+    /// Fails with `ERROR_BAD_ARGUMENTS` if it is missing or doesn't parse.
+    pub fn parse<T: FromStr>(&self, name: &str) -> Result<T> {
+        self.get(name).and_then(|v| v.parse().ok()).ok_or_else(|| {
+            Error::new(
+                ERROR_BAD_ARGUMENTS.to_hresult(),
+                format!("Invalid route parameter {name} in {}", self.path),
+            )
+        })
+    }
+}
+
+enum Segment {
+    Literal(String),
+    Param(String),
+}
+
+struct Pattern {
+    source: String,
+    segments: Box<[Segment]>,
+    router: OnceLock<std::sync::Weak<Routes>>,
+}
+
+/// A route pattern like `issue/{id}`, split at `/`.
+///
+/// Register it with [`PageRouter::route`], and create routes to its pages with [`RoutePattern::at`].
+/// A pattern belongs to the first router it is registered with.
+/// Clones are the same pattern.
+#[derive(Clone)]
+pub struct RoutePattern(Arc<Pattern>);
+
+impl RoutePattern {
+    /// Creates a pattern.
+    ///
+    /// Segments of the pattern are separated by `/`, and `{name}` segments capture any non-empty segment,
+    /// e.g. `issue/{id}` matches `issue/42`.
+    pub fn new(pattern: &str) -> Self {
+        let segments = pattern
+            .split('/')
+            .map(
+                |s| match s.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+                    Some(name) => Segment::Param(name.to_string()),
+                    None => Segment::Literal(s.to_string()),
+                },
+            )
+            .collect();
+        RoutePattern(Arc::new(Pattern {
+            source: pattern.to_string(),
+            segments,
+            router: OnceLock::new(),
+        }))
+    }
+
+    /// Creates a route to the page with the parameters of the pattern set to `values`, in order.
+    ///
+    /// Fails with `ERROR_BAD_ARGUMENTS` if the number of values doesn't match the parameters,
+    /// a value is empty or contains `/`, or the page id is matched by a route added before this one,
+    /// e.g. `issue/new` for `issue/{id}`.
+    /// Fails with `ERROR_NOT_FOUND` if the pattern isn't registered with a router which is alive.
+    pub fn at(&self, values: &[&dyn Display]) -> Result<Route> {
+        let invalid = |message: String| Error::new(ERROR_BAD_ARGUMENTS.to_hresult(), message);
+        let mut values = values.iter();
+        let mut segments = Vec::with_capacity(self.0.segments.len());
+        for segment in self.0.segments.iter() {
+            match segment {
+                Segment::Literal(literal) => segments.push(literal.clone()),
+                Segment::Param(name) => {
+                    let value = values
+                        .next()
+                        .ok_or_else(|| invalid(format!("Missing route parameter {name}")))?
+                        .to_string();
+                    if value.is_empty() || value.contains('/') {
+                        return Err(invalid(format!(
+                            "Invalid route parameter {name}: {value:?}"
+                        )));
+                    }
+                    segments.push(value);
+                }
+            }
+        }
+        if values.next().is_some() {
+            return Err(invalid("Too many route parameters".to_string()));
+        }
+        let path = segments.join("/");
+        let router = self
+            .0
+            .router
+            .get()
+            .and_then(|r| r.upgrade())
+            .ok_or_else(|| {
+                Error::new(
+                    ERROR_NOT_FOUND.to_hresult(),
+                    format!("Route pattern {} isn't registered", self.0.source),
+                )
+            })?;
+        let routes = read(&router);
+        let first = routes.iter().find(|(p, _)| p.matches(&path).is_some());
+        match first {
+            Some((pattern, _)) if !Arc::ptr_eq(&pattern.0, &self.0) => Err(invalid(format!(
+                "Page {path} is routed to {} instead of {}",
+                pattern.0.source, self.0.source
+            ))),
+            _ => Ok(Route { path }),
+        }
+    }
+
+    fn matches(&self, path: &str) -> Option<RouteParams> {
+        let segments: Vec<&str> = path.split('/').collect();
+        if segments.len() != self.0.segments.len() {
+            return None;
+        }
+        let mut params = Vec::new();
+        for (segment, value) in self.0.segments.iter().zip(segments) {
+            match segment {
+                Segment::Literal(literal) if literal == value => {}
+                Segment::Param(name) if !value.is_empty() => {
+                    params.push((name.clone(), value.to_string()));
+                }
+                _ => return None,
+            }
+        }
+        Some(RouteParams {
+            path: path.to_string(),
+            params,
+        })
+    }
+}
+
+type PageFactory = Arc<dyn Send + Sync + Fn(&RouteParams) -> Result<ICommand>>;
+type Routes = RwLock<Vec<(RoutePattern, PageFactory)>>;
+
+/// Reads the routes of a router.
+///
+/// They are only written by [`PageRouter::route`], which can't leave them half-updated,
+/// so a poisoned lock is recovered.
+fn read(routes: &Routes) -> std::sync::RwLockReadGuard<'_, Vec<(RoutePattern, PageFactory)>> {
+    routes.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Builds pages on demand from their ids.
+///
+/// Routes are matched in the order they were added.
+/// A built page is reused while it is alive, and built again once it is dropped.
+pub struct PageRouter {
+    routes: Arc<Routes>,
+    pages: Mutex<HashMap<String, Weak<ICommand>>>,
+}
+
+impl PageRouter {
+    /// Creates a router with no routes.
+    pub fn new() -> Self {
+        PageRouter {
+            routes: Arc::new(RwLock::new(Vec::new())),
+            pages: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Adds a route building pages whose ids match `pattern`.
+    ///
+    /// Routes created from `pattern` are checked against this router,
+    /// unless the pattern was registered with another router before.
+    pub fn route<F>(self, pattern: &RoutePattern, factory: F) -> Self
+    where
+        F: Send + Sync + Fn(&RouteParams) -> Result<ICommand> + 'static,
+    {
+        let _ = pattern.0.router.set(Arc::downgrade(&self.routes));
+        self.routes
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push((pattern.clone(), Arc::new(factory)));
+        self
+    }
+
+    /// Returns the page with `id`, building it if it isn't alive.
+    ///
+    /// The page is built without holding a lock, so factories may resolve other pages.
+    /// If the same page is built concurrently, the first one stored is returned.
+    /// Fails with `ERROR_NOT_FOUND` if no route matches `id`.
+    pub fn resolve(&self, id: &HSTRING) -> Result<ICommand> {
+        let path = id.to_string_lossy();
+        if let Some(page) = self.lock()?.get(&path).and_then(Weak::upgrade) {
+            return Ok(page);
+        }
+        let (params, factory) = read(&self.routes)
+            .iter()
+            .find_map(|(pattern, factory)| pattern.matches(&path).map(|p| (p, factory.clone())))
+            .ok_or_else(|| {
+                Error::new(
+                    ERROR_NOT_FOUND.to_hresult(),
+                    format!("No route matches page {path}"),
+                )
+            })?;
+        let page = factory(&params)?;
+        let mut pages = self.lock()?;
+        if let Some(existing) = pages.get(&path).and_then(Weak::upgrade) {
+            return Ok(existing);
+        }
+        pages.retain(|_, page| page.upgrade().is_some());
+        pages.insert(path, page.downgrade()?);
+        Ok(page)
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, HashMap<String, Weak<ICommand>>>> {
+        self.pages
+            .lock()
+            .map_err(|_| Error::from(ERROR_LOCK_VIOLATION))
+    }
+}
+
+impl Default for PageRouter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::BaseCommandBuilder;
+    use crate::utils::ComBuilder;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn page(id: &str) -> Result<ICommand> {
+        Ok(BaseCommandBuilder::new().id(id).build().to_interface())
+    }
+
+    #[test]
+    fn at_fills_parameters_in_order() {
+        let pattern = RoutePattern::new("repo/{owner}/{name}");
+        let _router = PageRouter::new().route(&pattern, |p| page(p.path()));
+        let route = pattern.at(&[&"me", &42]).unwrap();
+        assert_eq!(route.path(), "repo/me/42");
+        let invalid: [&[&dyn Display]; 4] = [&[&"me"], &[&"me", &1, &2], &[&"", &1], &[&"a/b", &1]];
+        for values in invalid {
+            let error = pattern.at(values).unwrap_err();
+            assert_eq!(error.code(), ERROR_BAD_ARGUMENTS.to_hresult());
+        }
+    }
+
+    #[test]
+    fn matches_captures_parameters() {
+        let pattern = RoutePattern::new("issue/{id}");
+        let params = pattern.matches("issue/42").unwrap();
+        assert_eq!(params.path(), "issue/42");
+        assert_eq!(params.get("id"), Some("42"));
+        assert_eq!(params.parse::<u32>("id").unwrap(), 42);
+        assert!(params.parse::<u32>("name").is_err());
+        assert!(pattern.matches("issue/").is_none());
+        assert!(pattern.matches("issue/42/comments").is_none());
+        assert!(pattern.matches("issues/42").is_none());
+    }
+
+    #[test]
+    fn at_rejects_unregistered_and_shadowed_routes() {
+        let new = RoutePattern::new("issue/new");
+        let issue = RoutePattern::new("issue/{id}");
+        assert_eq!(
+            issue.at(&[&42]).unwrap_err().code(),
+            ERROR_NOT_FOUND.to_hresult()
+        );
+        let router = PageRouter::new()
+            .route(&new, |_| page("new"))
+            .route(&issue, |p| page(p.path()));
+        assert_eq!(new.at(&[]).unwrap().path(), "issue/new");
+        assert_eq!(
+            issue.at(&[&"new"]).unwrap_err().code(),
+            ERROR_BAD_ARGUMENTS.to_hresult()
+        );
+        drop(router);
+        assert_eq!(
+            new.at(&[]).unwrap_err().code(),
+            ERROR_NOT_FOUND.to_hresult()
+        );
+    }
+
+    #[test]
+    fn resolve_reuses_live_pages() {
+        let built = Arc::new(AtomicUsize::new(0));
+        let issue = RoutePattern::new("issue/{id}");
+        let router = PageRouter::new().route(&issue, {
+            let built = built.clone();
+            move |p| {
+                built.fetch_add(1, Ordering::Relaxed);
+                page(&format!("page {}", p.parse::<u32>("id")?))
+            }
+        });
+        let id = HSTRING::from(issue.at(&[&7]).unwrap().path());
+        let first = router.resolve(&id).unwrap();
+        assert_eq!(first.Id().unwrap(), "page 7");
+        let again = router.resolve(&id).unwrap();
+        assert_eq!(again, first);
+        assert_eq!(built.load(Ordering::Relaxed), 1);
+        drop((first, again));
+        router.resolve(&id).unwrap();
+        assert_eq!(built.load(Ordering::Relaxed), 2);
+        let error = router.resolve(&"issues".into()).unwrap_err();
+        assert_eq!(error.code(), ERROR_NOT_FOUND.to_hresult());
+    }
+}
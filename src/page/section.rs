@@ -0,0 +1,188 @@
+//! Grouping and ordering of [`ListPage`][`super::list::ListPage`] items by section.
+//!
+//! Command Palette only draws a section header when consecutive items change section,
+//! so items of one section must be contiguous. [`SectionedItems`] keeps them that way.
+
+use std::sync::Mutex;
+
+use windows::Win32::Foundation::ERROR_LOCK_VIOLATION;
+use windows_core::{ComObject, Error, HSTRING, Result};
+
+use super::list::{ListItem, ListPage_Impl};
+
+/// How sections are ordered relative to each other.
+#[derive(Debug, Clone, Default)]
+pub enum SectionOrder {
+    /// Sections appear in the order they were first added.
+    #[default]
+    Insertion,
+    /// Sections appear in the given order.
+    ///
+    /// Sections not in the list follow in the order they were first added.
+    Explicit(Vec<HSTRING>),
+    /// Sections are sorted alphabetically, ignoring case.
+    Alphabetical,
+    /// Sections are sorted by the highest score of their items, highest first.
+    Score,
+}
+
+/// How items are ordered within a section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ItemOrder {
+    /// Items appear in the order they were added.
+    #[default]
+    Insertion,
+    /// Items are sorted by score, highest first.
+    Score,
+    /// Items are sorted alphabetically by title, ignoring case.
+    Title,
+}
+
+struct SectionedEntry {
+    score: f64,
+    item: ComObject<ListItem>,
+}
+
+/// A collection of list items grouped by section.
+///
+/// Add, remove and rescore items incrementally,
+/// then write the grouped result into a page with [`SectionedItems::apply`].
+///
+/// Items are grouped by their current [`IListItem::Section`][`crate::bindings::IListItem::Section`],
+/// so an item moves to another section when it is changed with
+/// [`ListItem_Impl::section_mut`][`super::list::ListItem_Impl::section_mut`], on the next apply.
+pub struct SectionedItems {
+    section_order: SectionOrder,
+    item_order: ItemOrder,
+    entries: Mutex<Vec<SectionedEntry>>,
+}
+
+impl SectionedItems {
+    /// Creates an empty collection with the given section order.
+    pub fn new(section_order: SectionOrder) -> Self {
+        SectionedItems {
+            section_order,
+            item_order: ItemOrder::Insertion,
+            entries: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Sets how items are ordered within a section.
+    pub fn item_order(mut self, item_order: ItemOrder) -> Self {
+        self.item_order = item_order;
+        self
+    }
+
+    fn entries(&self) -> Result<std::sync::MutexGuard<'_, Vec<SectionedEntry>>> {
+        self.entries
+            .lock()
+            .map_err(|_| Error::from(ERROR_LOCK_VIOLATION))
+    }
+
+    /// Adds an item to `section` with the given score.
+    ///
+    /// [`IListItem::Section`][`crate::bindings::IListItem::Section`] of the item is updated to `section`.
+    pub fn insert(
+        &self,
+        section: impl Into<HSTRING>,
+        score: f64,
+        item: ComObject<ListItem>,
+    ) -> Result<()> {
+        let section = section.into();
+        if *item.section()? != section {
+            *item.section_mut()? = section.clone();
+        }
+        self.entries()?.push(SectionedEntry { score, item });
+        Ok(())
+    }
+
+    /// Removes an item, returning whether it was present.
+    pub fn remove(&self, item: &ComObject<ListItem>) -> Result<bool> {
+        let mut entries = self.entries()?;
+        let len = entries.len();
+        entries.retain(|e| !std::ptr::eq(e.item.get(), item.get()));
+        Ok(entries.len() != len)
+    }
+
+    /// Updates the score of an item, returning whether it was present.
+    pub fn set_score(&self, item: &ComObject<ListItem>, score: f64) -> Result<bool> {
+        let mut entries = self.entries()?;
+        match entries
+            .iter_mut()
+            .find(|e| std::ptr::eq(e.item.get(), item.get()))
+        {
+            Some(entry) => {
+                entry.score = score;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Removes all items.
+    pub fn clear(&self) -> Result<()> {
+        self.entries()?.clear();
+        Ok(())
+    }
+
+    /// Returns the items grouped by section and ordered.
+    pub fn sorted(&self) -> Result<Vec<ComObject<ListItem>>> {
+        let entries = self.entries()?;
+        let mut sections: Vec<(HSTRING, Vec<&SectionedEntry>)> = Vec::new();
+        for entry in entries.iter() {
+            let section = entry.item.section()?.clone();
+            match sections.iter_mut().find(|(s, _)| *s == section) {
+                Some((_, group)) => group.push(entry),
+                None => sections.push((section, vec![entry])),
+            }
+        }
+
+        match &self.section_order {
+            SectionOrder::Insertion => {}
+            SectionOrder::Explicit(order) => sections
+                .sort_by_key(|(s, _)| order.iter().position(|o| o == s).unwrap_or(usize::MAX)),
+            SectionOrder::Alphabetical => {
+                sections.sort_by_cached_key(|(s, _)| s.to_string_lossy().to_lowercase())
+            }
+            SectionOrder::Score => sections.sort_by(|(_, a), (_, b)| {
+                let best = |g: &Vec<&SectionedEntry>| {
+                    g.iter().map(|e| e.score).fold(f64::NEG_INFINITY, f64::max)
+                };
+                best(b).total_cmp(&best(a))
+            }),
+        }
+
+        let mut items = Vec::with_capacity(entries.len());
+        for (_, mut group) in sections {
+            match self.item_order {
+                ItemOrder::Insertion => {}
+                ItemOrder::Score => group.sort_by(|a, b| b.score.total_cmp(&a.score)),
+                ItemOrder::Title => {
+                    let mut titled = Vec::with_capacity(group.len());
+                    for entry in group {
+                        titled.push((entry.item.title()?.to_string_lossy().to_lowercase(), entry));
+                    }
+                    titled.sort_by(|a, b| a.0.cmp(&b.0));
+                    group = titled.into_iter().map(|(_, e)| e).collect();
+                }
+            }
+            items.extend(group.into_iter().map(|e| e.item.clone()));
+        }
+        Ok(items)
+    }
+
+    /// Replaces the items of `page` with the grouped items.
+    ///
+    /// Emits a single items-changed notification.
+    pub fn apply(&self, page: &ListPage_Impl) -> Result<()> {
+        let items = self.sorted()?;
+        *page.items_mut()? = items;
+        Ok(())
+    }
+}
+
+impl Default for SectionedItems {
+    fn default() -> Self {
+        Self::new(SectionOrder::default())
+    }
+}
@@ -0,0 +1,304 @@
+//! Platform side effects performed by common commands.
+//!
+//! Commands in [`crate::cmd::common`] don't call the system directly,
+//! but go through a [`Platform`], which defaults to [`Win32Platform`].
+//! Inject a [`MockPlatform`] into their builders to observe what a command would do
+//! without touching the clipboard, the shell or the file explorer, e.g. in tests:
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use cmdpal::platform::{MockPlatform, PlatformAction};
+//! use cmdpal::prelude::*;
+//!
+//! let platform = Arc::new(MockPlatform::new());
+//! let cmd = CopyTextCommandBuilder::new("hello")
+//!     .platform(platform.clone())
+//!     .build();
+//! // ... invoke `cmd` ...
+//! assert_eq!(platform.actions(), [PlatformAction::SetClipboardText("hello".into())]);
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use windows_core::{Error, HSTRING, Result};
+
+/// System operations used by common commands.
+pub trait Platform: Send + Sync {
+    /// Puts `text` on the clipboard.
+    fn set_clipboard_text(&self, text: &HSTRING) -> Result<()>;
+
+    /// Opens `target`, e.g. a URL or a file, with its default handler.
+    fn open_in_shell(&self, target: &str) -> Result<()>;
+
+    /// Shows `path` selected in the file explorer.
+    fn reveal_file(&self, path: &Path) -> Result<()>;
+}
+
+/// Returns the default platform, [`Win32Platform`].
+#[cfg(windows)]
+pub fn default_platform() -> Arc<dyn Platform> {
+    Arc::new(Win32Platform)
+}
+
+/// Returns the default platform, which fails every operation with `E_NOTIMPL` outside of Windows.
+#[cfg(not(windows))]
+pub fn default_platform() -> Arc<dyn Platform> {
+    Arc::new(MockPlatform::failing(Error::from(
+        windows::Win32::Foundation::E_NOTIMPL,
+    )))
+}
+
+/// [`Platform`] implementation calling Win32 APIs.
+#[cfg(windows)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Win32Platform;
+
+#[cfg(windows)]
+impl Platform for Win32Platform {
+    fn set_clipboard_text(&self, text: &HSTRING) -> Result<()> {
+        clipboard_helper::set_clipboard_text(text.clone())
+    }
+
+    fn open_in_shell(&self, target: &str) -> Result<()> {
+        shell_helper::open_in_shell(target)
+    }
+
+    fn reveal_file(&self, path: &Path) -> Result<()> {
+        use windows::Win32::Foundation::ERROR_FILE_INVALID;
+
+        let path = path
+            .canonicalize()
+            .map_err(|_| Error::from(ERROR_FILE_INVALID))?;
+        match path.try_exists() {
+            Ok(true) => explorer_helper::reveal_file(&path.to_string_lossy().replace("/", r"\")),
+            _ => Err(Error::from(ERROR_FILE_INVALID)),
+        }
+    }
+}
+
+/// An operation recorded by [`MockPlatform`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlatformAction {
+    /// [`Platform::set_clipboard_text`] was called with the text.
+    SetClipboardText(HSTRING),
+    /// [`Platform::open_in_shell`] was called with the target.
+    OpenInShell(String),
+    /// [`Platform::reveal_file`] was called with the path.
+    RevealFile(PathBuf),
+}
+
+/// [`Platform`] implementation which records operations instead of performing them.
+#[derive(Debug, Default)]
+pub struct MockPlatform {
+    actions: Mutex<Vec<PlatformAction>>,
+    error: Option<Error>,
+}
+
+impl MockPlatform {
+    /// Creates a mock whose operations succeed.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a mock whose operations are recorded, then fail with `error`.
+    pub fn failing(error: Error) -> Self {
+        MockPlatform {
+            actions: Mutex::new(Vec::new()),
+            error: Some(error),
+        }
+    }
+
+    /// Returns the recorded operations, in order.
+    pub fn actions(&self) -> Vec<PlatformAction> {
+        self.actions
+            .lock()
+            .map(|actions| actions.clone())
+            .unwrap_or_default()
+    }
+
+    /// Returns and forgets the recorded operations.
+    pub fn take(&self) -> Vec<PlatformAction> {
+        self.actions
+            .lock()
+            .map(|mut actions| std::mem::take(&mut *actions))
+            .unwrap_or_default()
+    }
+
+    fn record(&self, action: PlatformAction) -> Result<()> {
+        if let Ok(mut actions) = self.actions.lock() {
+            actions.push(action);
+        }
+        match &self.error {
+            Some(error) => Err(error.clone()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Platform for MockPlatform {
+    fn set_clipboard_text(&self, text: &HSTRING) -> Result<()> {
+        self.record(PlatformAction::SetClipboardText(text.clone()))
+    }
+
+    fn open_in_shell(&self, target: &str) -> Result<()> {
+        self.record(PlatformAction::OpenInShell(target.to_string()))
+    }
+
+    fn reveal_file(&self, path: &Path) -> Result<()> {
+        self.record(PlatformAction::RevealFile(path.to_path_buf()))
+    }
+}
+
+#[cfg(windows)]
+mod clipboard_helper {
+    use windows::Win32::Foundation::{E_FAIL, E_POINTER, ERROR_LOCKED, GlobalFree, HANDLE};
+    use windows::Win32::System::Com::{COINIT_APARTMENTTHREADED, CoInitializeEx};
+    use windows::Win32::System::DataExchange::{
+        CloseClipboard, EmptyClipboard, OpenClipboard, SetClipboardData,
+    };
+    use windows::Win32::System::Memory::{GHND, GlobalAlloc, GlobalLock, GlobalUnlock};
+    use windows::Win32::System::Ole::CF_UNICODETEXT;
+    use windows_core::{HSTRING, Result};
+
+    pub(super) fn set_clipboard_text(text: HSTRING) -> Result<()> {
+        // start a new thread with STA
+        std::thread::spawn(move || {
+            const RETRY_COUNT: usize = 5;
+            let mut retries = 0;
+            let mut result = E_POINTER.ok();
+            while retries < RETRY_COUNT {
+                result = set_clipboard_text_sta(&text);
+                if result.is_ok() {
+                    return result;
+                }
+                retries += 1;
+            }
+            return result;
+        })
+        .join()
+        .map_err(|_| windows_core::Error::from(E_FAIL))??;
+        Ok(())
+    }
+
+    fn set_clipboard_text_sta(text: &HSTRING) -> Result<()> {
+        unsafe {
+            CoInitializeEx(None, COINIT_APARTMENTTHREADED).ok()?;
+            let mem = GlobalAlloc(GHND, size_of::<u16>() * (text.len() + 1))?;
+            let ptr = GlobalLock(mem) as *mut u16;
+            if ptr.is_null() {
+                return E_POINTER.ok();
+            }
+            ptr.copy_from((*text).as_ptr(), text.len());
+            ptr.offset(text.len() as isize).write(0);
+
+            let result = (|| -> Result<()> {
+                match GlobalUnlock(mem) {
+                    Ok(_) => ERROR_LOCKED.ok()?,
+                    Err(e) if e.code().0 != 0 => Err(e)?,
+                    Err(_) => {}
+                };
+                OpenClipboard(None)?;
+                EmptyClipboard()?;
+                SetClipboardData(CF_UNICODETEXT.0.into(), Some(HANDLE(mem.0)))?;
+                CloseClipboard()?;
+                Ok(())
+            })();
+            if result.is_err() {
+                GlobalFree(Some(mem))?;
+                return result;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(windows)]
+mod shell_helper {
+    use windows::Win32::UI::Shell::{SHELLEXECUTEINFOW, ShellExecuteExW};
+    use windows::Win32::UI::{Shell::SEE_MASK_NOCLOSEPROCESS, WindowsAndMessaging::SW_SHOWNORMAL};
+    use windows_core::{HSTRING, PCWSTR};
+
+    pub(super) fn open_in_shell(target: &str) -> windows_core::Result<()> {
+        // Keep the string alive until `ShellExecuteExW` returns.
+        let target = HSTRING::from(target);
+        let mut sei = SHELLEXECUTEINFOW::default();
+        sei.cbSize = std::mem::size_of::<SHELLEXECUTEINFOW>() as u32;
+        sei.fMask = SEE_MASK_NOCLOSEPROCESS;
+        sei.lpFile = PCWSTR::from_raw(target.as_ptr());
+        sei.nShow = SW_SHOWNORMAL.0;
+
+        unsafe { ShellExecuteExW(&mut sei) }
+    }
+}
+
+#[cfg(windows)]
+mod explorer_helper {
+    use windows::Win32::UI::Shell::{SHELLEXECUTEINFOW, ShellExecuteExW};
+    use windows::Win32::UI::{Shell::SEE_MASK_NOCLOSEPROCESS, WindowsAndMessaging::SW_SHOWNORMAL};
+    use windows_core::{HSTRING, PCWSTR, w};
+
+    pub(super) fn reveal_file(target: &str) -> windows_core::Result<()> {
+        // Keep the string alive until `ShellExecuteExW` returns.
+        let params = HSTRING::from(format!("/select,\"{}\"", target));
+        let mut sei = SHELLEXECUTEINFOW::default();
+        sei.cbSize = std::mem::size_of::<SHELLEXECUTEINFOW>() as u32;
+        sei.fMask = SEE_MASK_NOCLOSEPROCESS;
+        sei.lpFile = w!("explorer.exe");
+        sei.lpParameters = PCWSTR::from_raw(params.as_ptr());
+        sei.nShow = SW_SHOWNORMAL.0;
+
+        unsafe { ShellExecuteExW(&mut sei) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::common::CopyTextCommandBuilder;
+    use crate::utils::ComBuilder;
+    use windows::Win32::Foundation::E_ACCESSDENIED;
+    use windows_core::IInspectable;
+
+    #[test]
+    fn mock_records_actions() {
+        let platform = MockPlatform::new();
+        platform.set_clipboard_text(&"hello".into()).unwrap();
+        platform.open_in_shell("https://example.com").unwrap();
+        platform.reveal_file(Path::new("a.txt")).unwrap();
+        assert_eq!(
+            platform.take(),
+            [
+                PlatformAction::SetClipboardText("hello".into()),
+                PlatformAction::OpenInShell("https://example.com".to_string()),
+                PlatformAction::RevealFile(PathBuf::from("a.txt")),
+            ]
+        );
+        assert!(platform.actions().is_empty());
+    }
+
+    #[test]
+    fn failing_mock_records_then_fails() {
+        let platform = MockPlatform::failing(Error::from(E_ACCESSDENIED));
+        let error = platform.open_in_shell("notepad").unwrap_err();
+        assert_eq!(error.code(), E_ACCESSDENIED);
+        assert_eq!(
+            platform.actions(),
+            [PlatformAction::OpenInShell("notepad".to_string())]
+        );
+    }
+
+    #[test]
+    fn copy_text_uses_platform() {
+        let platform = Arc::new(MockPlatform::new());
+        let cmd = CopyTextCommandBuilder::new("hello")
+            .platform(platform.clone())
+            .build();
+        let sender: IInspectable = cmd.to_interface();
+        cmd.invoke(&sender).unwrap();
+        assert_eq!(
+            platform.actions(),
+            [PlatformAction::SetClipboardText("hello".into())]
+        );
+    }
+}
@@ -1,9 +1,13 @@
 //! Common types and traits used across the cmdpal library.
 
 pub use crate::{
+    cancel::CancellationToken,
     cmd::{
-        BaseCommand, BaseCommandBuilder, CommandResult, InvokableCommand,
-        common::{CopyTextCommandBuilder, OpenUrlCommandBuilder, RevealFileCommandBuilder},
+        BaseCommand, BaseCommandBuilder, CommandResult, InvokableCommand, WhenBusy,
+        common::{
+            CopyTextCommandBuilder, OpenUrlCommandBuilder, RevealFileCommandBuilder,
+            RunProcessCommandBuilder,
+        },
     },
     cmd_item::{CommandItem, CommandItemBuilder},
     cmd_provider::{CommandProvider, CommandProviderBuilder},
@@ -13,13 +17,16 @@ pub use crate::{
         Details, DetailsBuilder, DetailsData, DetailsElement, DetailsLink, DetailsLinkBuilder,
         DetailsSeparator, DetailsTags, DetailsTagsBuilder, Tag, TagBuilder,
     },
+    error::{ErrorDisplay, ErrorPolicy},
     ext::Extension,
     ext_registry::ExtRegistry,
     fallback::FallbackCommandItem,
     filter::{Filter, FilterItem, FilterSeparator, Filters},
+    frecency::Frecency,
     host::{
         LogMessage, MessageState, ProgressState, ProgressStateBuilder, StatusContext,
         StatusMessage, StatusMessageBuilder, hide_status, log_message, show_status,
+        show_status_for,
     },
     icon::{IconData, IconInfo},
     page::{
@@ -27,7 +34,9 @@ pub use crate::{
         content::{ContentPage, ContentPageBuilder},
         dyn_list::{DynamicListPage, DynamicListPageBuilder},
         list::{ListItem, ListItemBuilder, ListPage, ListPageBuilder},
+        router::{PageRouter, Route, RouteParams, RoutePattern},
     },
+    query::{Query, QuerySyntax},
     settings::{
         SettingBasePropModifier, Choice, ChoiceSetSetting, CommandSettings, JsonCommandSettings,
         NumberSetting, TextSetting, ToggleSetting,
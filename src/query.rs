@@ -0,0 +1,369 @@
+//! Structured search query syntax for dynamic pages.
+//!
+//! A query like `is:open -label:wontfix author:me "exact phrase" >cmd foo` is split into [`Token`]s:
+//!
+//! - `key:value` fields, whose value may be quoted, e.g. `title:"hello world"`,
+//! - quoted phrases, e.g. `"exact phrase"`,
+//! - prefix shortcuts registered with [`QuerySyntax::shortcut`], e.g. `>cmd` or `@user`,
+//! - plain words.
+//!
+//! Any token can be negated with a leading `-`.
+//!
+//! Attach a syntax to a page with [`DynamicListPageBuilder::query_fn`][`crate::page::dyn_list::DynamicListPageBuilder::query_fn`].
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// The meaning of a single query token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Term {
+    /// A plain word.
+    Word(String),
+    /// A quoted phrase, without the quotes.
+    Phrase(String),
+    /// A `key:value` pair.
+    Field {
+        /// Key before the colon.
+        key: String,
+        /// Value after the colon, without quotes. Empty while the value is being typed.
+        value: String,
+    },
+    /// A prefix shortcut like `>cmd` or `@user`.
+    Shortcut {
+        /// The prefix character.
+        prefix: char,
+        /// Text after the prefix, without quotes.
+        value: String,
+    },
+}
+
+/// A single token of a [`Query`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    /// Meaning of the token.
+    pub term: Term,
+    /// Whether the token was prefixed with `-`.
+    pub negated: bool,
+    /// Byte range of the token in the query text, including any `-` prefix.
+    pub span: Range<usize>,
+}
+
+/// A parsed search query.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Query {
+    /// The query text as typed.
+    pub text: String,
+    /// Tokens of the query, in order.
+    pub tokens: Vec<Token>,
+}
+
+impl Query {
+    /// Returns whether the query has no tokens.
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    fn positive(&self) -> impl Iterator<Item = &Term> {
+        self.tokens.iter().filter(|t| !t.negated).map(|t| &t.term)
+    }
+
+    fn negative(&self) -> impl Iterator<Item = &Term> {
+        self.tokens.iter().filter(|t| t.negated).map(|t| &t.term)
+    }
+
+    /// Returns the words and phrases which are not negated.
+    pub fn terms(&self) -> impl Iterator<Item = &str> {
+        self.positive().filter_map(free_text)
+    }
+
+    /// Returns the negated words and phrases.
+    pub fn excluded_terms(&self) -> impl Iterator<Item = &str> {
+        self.negative().filter_map(free_text)
+    }
+
+    /// Returns the value of the first field with `key` which is not negated.
+    ///
+    /// Keys are compared ignoring ASCII case.
+    pub fn field<'a>(&'a self, key: &'a str) -> Option<&'a str> {
+        self.fields(key).next()
+    }
+
+    /// Returns the values of all fields with `key` which are not negated.
+    pub fn fields<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.positive().filter_map(move |t| field_value(t, key))
+    }
+
+    /// Returns the values of all negated fields with `key`.
+    pub fn excluded_fields<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.negative().filter_map(move |t| field_value(t, key))
+    }
+
+    /// Returns the value of the first shortcut with `prefix` which is not negated.
+    pub fn shortcut(&self, prefix: char) -> Option<&str> {
+        self.positive().find_map(|t| match t {
+            Term::Shortcut { prefix: p, value } if *p == prefix => Some(value.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Returns the token being typed, i.e. the last token if the text doesn't end with whitespace.
+    pub fn partial(&self) -> Option<&Token> {
+        self.tokens.last().filter(|t| t.span.end == self.text.len())
+    }
+}
+
+fn free_text(term: &Term) -> Option<&str> {
+    match term {
+        Term::Word(text) | Term::Phrase(text) => Some(text),
+        _ => None,
+    }
+}
+
+fn field_value<'a>(term: &'a Term, key: &str) -> Option<&'a str> {
+    match term {
+        Term::Field { key: k, value } if k.eq_ignore_ascii_case(key) => Some(value),
+        _ => None,
+    }
+}
+
+fn unquote(text: &str) -> String {
+    let text = text.strip_prefix('"').unwrap_or(text);
+    text.strip_suffix('"').unwrap_or(text).to_string()
+}
+
+/// Rules for parsing and completing [`Query`]s.
+#[derive(Debug, Clone, Default)]
+pub struct QuerySyntax {
+    shortcuts: Vec<char>,
+    keys: Vec<String>,
+    values: HashMap<String, Vec<String>>,
+}
+
+impl QuerySyntax {
+    /// Creates a syntax which accepts any `key:value` field and no shortcuts.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a prefix shortcut, e.g. `'>'` or `'@'`.
+    pub fn shortcut(mut self, prefix: char) -> Self {
+        self.shortcuts.push(prefix);
+        self
+    }
+
+    /// Registers a field key with its known values, used for completion.
+    ///
+    /// Once any key is registered, only registered keys are parsed as fields,
+    /// and other `key:value` tokens are parsed as words, e.g. URLs.
+    pub fn key<I, S>(mut self, key: impl Into<String>, values: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let key = key.into();
+        self.values.insert(
+            key.to_ascii_lowercase(),
+            values.into_iter().map(Into::into).collect(),
+        );
+        self.keys.push(key);
+        self
+    }
+
+    /// Returns the known values of `key`.
+    pub fn values(&self, key: &str) -> &[String] {
+        self.values
+            .get(&key.to_ascii_lowercase())
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    fn is_key(&self, key: &str) -> bool {
+        !key.is_empty()
+            && key
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+            && (self.keys.is_empty() || self.keys.iter().any(|k| k.eq_ignore_ascii_case(key)))
+    }
+
+    /// Parses `text` into a [`Query`].
+    pub fn parse(&self, text: &str) -> Query {
+        let mut tokens = Vec::new();
+        let mut chars = text.char_indices().peekable();
+        loop {
+            while let Some(&(_, c)) = chars.peek()
+                && c.is_whitespace()
+            {
+                chars.next();
+            }
+            let Some(&(start, _)) = chars.peek() else {
+                break;
+            };
+            let mut end = text.len();
+            let mut quoted = false;
+            while let Some(&(i, c)) = chars.peek() {
+                if c == '"' {
+                    quoted = !quoted;
+                } else if c.is_whitespace() && !quoted {
+                    end = i;
+                    break;
+                }
+                chars.next();
+            }
+            tokens.push(self.parse_token(&text[start..end], start..end));
+        }
+        Query {
+            text: text.to_string(),
+            tokens,
+        }
+    }
+
+    fn parse_token(&self, raw: &str, span: Range<usize>) -> Token {
+        let (negated, body) = match raw.strip_prefix('-') {
+            Some(rest) if !rest.is_empty() => (true, rest),
+            _ => (false, raw),
+        };
+        let term = if body.starts_with('"') {
+            Term::Phrase(unquote(body))
+        } else if let Some(prefix) = body.chars().next()
+            && self.shortcuts.contains(&prefix)
+        {
+            Term::Shortcut {
+                prefix,
+                value: unquote(&body[prefix.len_utf8()..]),
+            }
+        } else if let Some((key, value)) = body.split_once(':')
+            && self.is_key(key)
+        {
+            Term::Field {
+                key: key.to_string(),
+                value: unquote(value),
+            }
+        } else {
+            Term::Word(body.to_string())
+        };
+        Token {
+            term,
+            negated,
+            span,
+        }
+    }
+
+    /// Completes the token being typed at the end of `text`.
+    ///
+    /// A word completes to a registered key, like `au` to `author:`,
+    /// and a field value completes to a known value of its key, like `is:op` to `is:open`.
+    /// Returns the whole completed text, or `None` if there is nothing to complete.
+    pub fn suggest(&self, text: &str) -> Option<String> {
+        let query = self.parse(text);
+        let token = query.partial()?;
+        let prefix = if token.negated { "-" } else { "" };
+        let completion = match &token.term {
+            Term::Word(word) => self
+                .keys
+                .iter()
+                .find(|k| starts_with_ignore_case(k, word))
+                .map(|k| format!("{prefix}{k}:"))?,
+            Term::Field { key, value } if !text[token.span.clone()].contains('"') => self
+                .values(key)
+                .iter()
+                .find(|v| v.len() > value.len() && starts_with_ignore_case(v, value))
+                .map(|v| format!("{prefix}{key}:{v}"))?,
+            _ => return None,
+        };
+        Some(format!("{}{}", &text[..token.span.start], completion))
+    }
+}
+
+fn starts_with_ignore_case(text: &str, prefix: &str) -> bool {
+    text.len() >= prefix.len()
+        && text.is_char_boundary(prefix.len())
+        && text[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(key: &str, value: &str) -> Term {
+        Term::Field {
+            key: key.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_fields() {
+        let syntax = QuerySyntax::new();
+        let query = syntax.parse("is:open -label:wontfix Author:me");
+        let terms: Vec<_> = query.tokens.iter().map(|t| (&t.term, t.negated)).collect();
+        assert_eq!(
+            terms,
+            [
+                (&field("is", "open"), false),
+                (&field("label", "wontfix"), true),
+                (&field("Author", "me"), false),
+            ]
+        );
+        assert_eq!(query.field("author"), Some("me"));
+        assert_eq!(
+            query.excluded_fields("label").collect::<Vec<_>>(),
+            ["wontfix"]
+        );
+        assert_eq!(query.tokens[1].span, 8..22);
+    }
+
+    #[test]
+    fn parses_quotes() {
+        let syntax = QuerySyntax::new().shortcut('@');
+        let query = syntax.parse(r#"title:"hello world" "exact phrase" @"some user" -"not this""#);
+        let terms: Vec<_> = query.tokens.iter().map(|t| &t.term).collect();
+        assert_eq!(
+            terms,
+            [
+                &field("title", "hello world"),
+                &Term::Phrase("exact phrase".to_string()),
+                &Term::Shortcut {
+                    prefix: '@',
+                    value: "some user".to_string(),
+                },
+                &Term::Phrase("not this".to_string()),
+            ]
+        );
+        assert_eq!(query.terms().collect::<Vec<_>>(), ["exact phrase"]);
+        assert_eq!(query.excluded_terms().collect::<Vec<_>>(), ["not this"]);
+    }
+
+    #[test]
+    fn unclosed_quote_runs_to_end() {
+        let query = QuerySyntax::new().parse(r#"foo "bar baz"#);
+        assert_eq!(query.tokens[1].term, Term::Phrase("bar baz".to_string()));
+        assert_eq!(query.partial(), Some(&query.tokens[1]));
+    }
+
+    #[test]
+    fn unregistered_keys_are_words() {
+        let syntax = QuerySyntax::new().key("is", ["open", "closed"]);
+        let query = syntax.parse("is:open https://example.com foo:bar");
+        let terms: Vec<_> = query.tokens.iter().map(|t| &t.term).collect();
+        assert_eq!(
+            terms,
+            [
+                &field("is", "open"),
+                &Term::Word("https://example.com".to_string()),
+                &Term::Word("foo:bar".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn suggests_keys_and_values() {
+        let syntax = QuerySyntax::new()
+            .key("author", Vec::<String>::new())
+            .key("is", ["open", "closed"]);
+        assert_eq!(syntax.suggest("foo au").as_deref(), Some("foo author:"));
+        assert_eq!(syntax.suggest("-is:op").as_deref(), Some("-is:open"));
+        assert_eq!(syntax.suggest("is:open"), None);
+        assert_eq!(syntax.suggest("is:op "), None);
+        assert_eq!(syntax.suggest(r#"is:"op"#), None);
+    }
+}
@@ -0,0 +1,182 @@
+//! Periodic refresh of COM objects, bound to their lifetime.
+//!
+//! A [`RefreshScheduler`] runs a closure at an interval against a target object,
+//! e.g. to update a [`MarkdownContent`][`crate::content::markdown::MarkdownContent`] showing live data:
+//!
+//! ```ignore
+//! RefreshScheduler::new(Duration::from_secs(1))
+//!     .pause_when_unobserved()
+//!     .start(&content, |content| {
+//!         *content.body_mut()? = current_time().into();
+//!         Ok(())
+//!     })?;
+//! ```
+//!
+//! Refreshing stops once the target is dropped, as the scheduler only holds a weak reference to it.
+//! The closure receives the target, so it must not capture a strong reference to it.
+
+use std::any::Any;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use crate::error::catch_panic;
+use crate::host::LogMessage;
+use crate::notify::Observable;
+use windows_core::{ComObject, ComObjectInner, IUnknown, IUnknownImpl, Interface as _, Result};
+
+/// Schedules periodic refreshes of a COM object.
+pub struct RefreshScheduler<T: ComObjectInner> {
+    interval: Duration,
+    jitter: Duration,
+    max_backoff: Option<Duration>,
+    is_observed: Option<fn(&T::Outer) -> bool>,
+}
+
+/// Handle to a running [`RefreshScheduler`].
+///
+/// Dropping the handle doesn't stop refreshing, refreshing stops once the target is dropped
+/// or [`RefreshHandle::stop`] is called.
+#[derive(Clone)]
+pub struct RefreshHandle {
+    stopped: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl RefreshHandle {
+    /// Stops refreshing. A running refresh finishes first.
+    pub fn stop(&self) {
+        let (stopped, wake) = &*self.stopped;
+        if let Ok(mut stopped) = stopped.lock() {
+            *stopped = true;
+        }
+        wake.notify_all();
+    }
+
+    /// Waits for `timeout`, returning whether refreshing was stopped meanwhile.
+    fn wait(&self, timeout: Duration) -> bool {
+        let (stopped, wake) = &*self.stopped;
+        let Ok(guard) = stopped.lock() else {
+            return true;
+        };
+        wake.wait_timeout_while(guard, timeout, |stopped| !*stopped)
+            .map(|(stopped, _)| *stopped)
+            .unwrap_or(true)
+    }
+}
+
+/// Returns a random duration in `[0, max)`.
+fn random_duration(max: Duration) -> Duration {
+    if max.is_zero() {
+        return Duration::ZERO;
+    }
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(
+        std::time::UNIX_EPOCH
+            .elapsed()
+            .unwrap_or_default()
+            .as_nanos(),
+    );
+    let nanos = max.as_nanos().min(u64::MAX as u128) as u64;
+    Duration::from_nanos(hasher.finish() % nanos)
+}
+
+impl<T> RefreshScheduler<T>
+where
+    T: ComObjectInner,
+    T::Outer: Any + IUnknownImpl<Impl = T>,
+{
+    /// Creates a scheduler which refreshes every `interval`.
+    pub fn new(interval: Duration) -> Self {
+        RefreshScheduler {
+            interval,
+            jitter: Duration::ZERO,
+            max_backoff: None,
+            is_observed: None,
+        }
+    }
+
+    /// Adds a random delay up to `jitter` to every interval,
+    /// so refreshes of many objects don't happen at once.
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Doubles the interval after each consecutive failed refresh, up to `max`.
+    ///
+    /// The interval is reset after a successful refresh.
+    pub fn backoff(mut self, max: Duration) -> Self {
+        self.max_backoff = Some(max);
+        self
+    }
+
+    /// Skips refreshes while no handler is subscribed to the change events of the target,
+    /// i.e. while Command Palette isn't showing it.
+    /// Handlers left behind by a host which exited don't count, see [`TrackedEvent::handler_count`][`crate::notify::TrackedEvent::handler_count`].
+    pub fn pause_when_unobserved(mut self) -> Self
+    where
+        T: Observable,
+    {
+        self.is_observed = Some(T::is_observed);
+        self
+    }
+
+    fn delay(&self, failures: u32) -> Duration {
+        let interval = match self.max_backoff {
+            Some(max) if failures > 0 => self
+                .interval
+                .saturating_mul(2u32.saturating_pow(failures.min(16)))
+                .min(max.max(self.interval)),
+            _ => self.interval,
+        };
+        interval + random_duration(self.jitter)
+    }
+
+    /// Starts refreshing `target` with `refresh` on a background thread.
+    ///
+    /// The first refresh happens after one interval.
+    /// Failed refreshes are logged, and panics are caught.
+    pub fn start<F>(self, target: &ComObject<T>, refresh: F) -> Result<RefreshHandle>
+    where
+        F: Send + FnMut(&T::Outer) -> Result<()> + 'static,
+        T::Outer: windows_core::ComObjectInterface<IUnknown>,
+        Self: Send + 'static,
+    {
+        let weak = target.to_interface::<IUnknown>().downgrade()?;
+        let handle = RefreshHandle {
+            stopped: Arc::new((Mutex::new(false), Condvar::new())),
+        };
+        let thread_handle = handle.clone();
+        let mut refresh = refresh;
+        std::thread::spawn(move || {
+            let mut failures = 0;
+            loop {
+                if thread_handle.wait(self.delay(failures)) {
+                    return;
+                }
+                let Some(target) = weak
+                    .upgrade()
+                    .and_then(|unknown| ComObject::<T>::cast_from(&unknown).ok())
+                else {
+                    return;
+                };
+                if self
+                    .is_observed
+                    .is_some_and(|is_observed| !is_observed(&target))
+                {
+                    continue;
+                }
+                match catch_panic(|| refresh(&target)) {
+                    Ok(()) => failures = 0,
+                    Err(e) => {
+                        failures += 1;
+                        LogMessage::warning(format!("Refresh failed: {}", e.message()).into())
+                            .log();
+                    }
+                }
+            }
+        });
+        Ok(handle)
+    }
+}
@@ -1,7 +1,10 @@
 //! Utility types and traits for Command Palette
 
 use crate::bindings::*;
+use std::marker::PhantomData;
 use std::ops::DerefMut;
+use std::path::Path;
+use std::sync::RwLock;
 use windows::Storage::Streams::{IBuffer, IBuffer_Impl};
 use windows::Win32::Foundation::E_NOTIMPL;
 use windows::Win32::System::WinRT::{IBufferByteAccess, IBufferByteAccess_Impl};
@@ -76,6 +79,70 @@ where
     arr
 }
 
+/// A cache of converted elements for building windows [`Array`]s.
+///
+/// Converting and filtering every element each time the host asks for a collection
+/// dominates CPU for large collections, so the converted elements are kept until invalidated.
+/// Every returned array still holds its own reference to each element, as the host owns them.
+///
+/// Elements are converted from values of the source type `S`, e.g. `ComObject<ListItem>`.
+pub(crate) struct ArrayCache<T: windows_core::Type<T>, S> {
+    cache: RwLock<Option<Vec<T::Default>>>,
+    source: PhantomData<fn() -> S>,
+}
+
+// SAFETY: The cached elements are only created from `S` values in `get_or_init`,
+// and `S: Send + Sync` means they are interfaces of free-threaded objects.
+unsafe impl<T: windows_core::Type<T>, S: Send + Sync> Send for ArrayCache<T, S> {}
+unsafe impl<T: windows_core::Type<T>, S: Send + Sync> Sync for ArrayCache<T, S> {}
+
+impl<T, S> ArrayCache<T, S>
+where
+    T: windows_core::Type<T>,
+    T::Default: Clone,
+    S: Send + Sync,
+{
+    pub(crate) fn new() -> Self {
+        ArrayCache {
+            cache: RwLock::new(None),
+            source: PhantomData,
+        }
+    }
+
+    /// Builds an array from the cached elements, filling the cache by converting the source elements
+    /// returned by `init` if it is empty.
+    ///
+    /// The caller should hold a read guard of the source collection while calling this,
+    /// so the cache can't be filled with a stale snapshot.
+    pub(crate) fn get_or_init<'a, I, F>(&self, init: impl FnOnce() -> I, convert: F) -> Array<T>
+    where
+        I: IntoIterator<Item = &'a S>,
+        F: Fn(&S) -> T::Default,
+        S: 'a,
+    {
+        if let Ok(guard) = self.cache.read()
+            && let Some(values) = guard.as_ref()
+        {
+            return Array::from_slice(values);
+        }
+        let values: Vec<T::Default> = init().into_iter().map(convert).collect();
+        let arr = Array::from_slice(&values);
+        if let Ok(mut guard) = self.cache.write() {
+            *guard = Some(values);
+        }
+        arr
+    }
+
+    /// Drops the cached elements.
+    ///
+    /// Should be called while still holding the write guard of the source collection.
+    pub(crate) fn invalidate(&self) {
+        if let Ok(mut guard) = self.cache.write() {
+            *guard = None;
+        }
+    }
+}
+
 impl From<Option<Color>> for OptionalColor {
     fn from(value: Option<Color>) -> Self {
         match value {
@@ -122,6 +189,20 @@ pub trait ComBuilder: Sized {
 #[allow(dead_code, reason = "Compile check only")]
 pub(crate) const fn assert_send_sync<T: Send + Sync>() {}
 
+/// Writes `data` to a temporary file next to `path`, then renames it over `path`,
+/// so a crash while writing can't leave a truncated file behind.
+///
+/// Parent directories are created if they do not exist.
+pub(crate) fn write_atomic(path: &Path, data: &str) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    std::fs::write(&temp, data)?;
+    std::fs::rename(&temp, path)
+}
+
 #[doc(hidden)]
 #[macro_export]
 macro_rules! _define_windows_core_interface_with_bindings_docs {
@@ -50,18 +50,17 @@ fn com_main() -> Result<()> {
             Ok(CommandResult::KeepOpen)
         })
         .build();
-    let task_box = md_box.clone();
-    // start a thread to update the content to current time
-    let _handle = std::thread::spawn(move || unsafe {
-        loop {
-            let time = Win32::System::SystemInformation::GetLocalTime();
+    // update the content to current time while it is shown
+    cmdpal::refresh::RefreshScheduler::new(std::time::Duration::from_secs(1))
+        .pause_when_unobserved()
+        .start(&md_box, move |md_box| {
+            let time = unsafe { Win32::System::SystemInformation::GetLocalTime() };
             let time = format!(
                 "{}-{}-{} {:02}:{:02}:{:02}",
                 time.wYear, time.wMonth, time.wDay, time.wHour, time.wMinute, time.wSecond
             );
-            if let Ok(mut body) = task_box.body_mut() {
-                *body = format!(
-                    r#"
+            *md_box.body_mut()? = format!(
+                r#"
 # Current Time
 {}
 
@@ -71,17 +70,15 @@ fn com_main() -> Result<()> {
 - Toggle: {:?}
 - Model: {:?}
 "#,
-                    time,
-                    token.lock().ok().map(|v| v.clone()).flatten(),
-                    temperature.lock().ok().map(|v| v.clone()).flatten(),
-                    toggle.lock().ok().map(|v| v.clone()).flatten(),
-                    model.lock().ok().map(|v| v.clone()).flatten()
-                )
-                .into();
-            }
-            std::thread::sleep(std::time::Duration::from_secs(1));
-        }
-    });
+                time,
+                token.lock().ok().map(|v| v.clone()).flatten(),
+                temperature.lock().ok().map(|v| v.clone()).flatten(),
+                toggle.lock().ok().map(|v| v.clone()).flatten(),
+                model.lock().ok().map(|v| v.clone()).flatten()
+            )
+            .into();
+            Ok(())
+        })?;
     let copy_sample_item: ComObject<CommandItem> = CommandItemBuilder::try_new(
         cmdpal::cmd::common::copy_text::CopyTextCommandBuilder::new(
             h!("This is a sample text to copy to clipboard").clone(),